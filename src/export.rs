@@ -0,0 +1,525 @@
+use crate::{font, is_staggered, ColorMap, Rgb8, SEPARATOR_COLOR, UNMAPPED_CHAR, UNMAPPED_NAME};
+use image::{Rgb, RgbImage};
+use std::{
+    collections::HashMap,
+    error::Error,
+    fmt::Write,
+    num::NonZeroU32,
+    path::{Path, PathBuf},
+};
+
+// The smallest cell that still fits a 1px outline plus a readable glyph.
+pub const MIN_CELL_SIZE: u32 = 8;
+// Outline of every labeled row and column, so they can be followed across a printout.
+const GRID_COLOR: Rgb<u8> = Rgb([200, 40, 40]);
+const LABEL_COLOR: Rgb<u8> = Rgb([255, 255, 255]);
+// Space between labels and the grid.
+const LABEL_PADDING: u32 = 2;
+
+// One printable tile of the pattern, as cut by `paginate`.
+pub struct Page {
+    // Where the tile's first row and first link sit in the whole pattern.
+    pub top: usize,
+    pub left: usize,
+    // (row, column) of this page in the grid of pages, and that grid's size.
+    pub position: (usize, usize),
+    pub grid: (usize, usize),
+    // Ragged like the pattern: rows shorter than the page's columns stay short or empty.
+    pub rows: Vec<Vec<Rgb8>>,
+}
+
+impl Page {
+    // Where the page goes in the whole pattern, 1-based like the rest of the UI. Sticks to
+    // characters the export font can draw.
+    pub fn header(&self) -> String {
+        let width = self.rows.iter().map(|r| r.len()).max().unwrap_or(0);
+        format!(
+            "Page {}-{} of {}x{}  rows {}-{}  links {}-{}",
+            self.position.0 + 1,
+            self.position.1 + 1,
+            self.grid.0,
+            self.grid.1,
+            self.top + 1,
+            self.top + self.rows.len(),
+            self.left + 1,
+            self.left + width
+        )
+    }
+
+    // `out.png` becomes `out-page1-2.png`.
+    pub fn path_for(&self, path: &Path) -> PathBuf {
+        let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+        let mut name = format!("{}-page{}-{}", stem, self.position.0 + 1, self.position.1 + 1);
+        if let Some(extension) = path.extension() {
+            name = format!("{}.{}", name, extension.to_string_lossy());
+        }
+        path.with_file_name(name)
+    }
+}
+
+// Tiles the pattern into pages of at most `rows_per_page` rows and `cols_per_page` links, where
+// neighboring pages share `overlap` rows or links so printouts can be lined up. Page tops stay on
+// even rows so every page staggers like the whole pattern, which can add a row of overlap. Pages
+// that would be empty, past the end of every row they cover, are left out.
+pub fn paginate(
+    rows: &[Vec<Rgb8>],
+    rows_per_page: usize,
+    cols_per_page: usize,
+    overlap: usize,
+) -> Result<Vec<Page>, Box<dyn Error>> {
+    let too_small = || {
+        format!(
+            "Pages need at least {} rows and {} links to overlap by {}",
+            overlap + 2,
+            overlap + 1,
+            overlap
+        )
+    };
+    // A step of zero is only a problem when there's more than one page to step to.
+    let starts = |total: usize, per_page: usize, step: usize| {
+        let (mut start, mut starts) = (0usize, vec![0]);
+        while start.saturating_add(per_page) < total {
+            if step == 0 {
+                return Err(too_small());
+            }
+            start += step;
+            starts.push(start);
+        }
+        Ok(starts)
+    };
+    let longest = rows.iter().map(|r| r.len()).max().unwrap_or(0);
+    let row_starts = starts(rows.len(), rows_per_page, rows_per_page.saturating_sub(overlap) / 2 * 2)?;
+    let col_starts = starts(longest, cols_per_page, cols_per_page.saturating_sub(overlap))?;
+
+    let mut pages = vec![];
+    for (page_row, top) in row_starts.iter().enumerate() {
+        for (page_col, left) in col_starts.iter().enumerate() {
+            let page_rows: Vec<Vec<Rgb8>> = rows[*top..top.saturating_add(rows_per_page).min(rows.len())]
+                .iter()
+                .map(|row| row.iter().skip(*left).take(cols_per_page).copied().collect())
+                .collect();
+            if page_rows.iter().all(|r| r.is_empty()) {
+                continue;
+            }
+            pages.push(Page {
+                top: *top,
+                left: *left,
+                position: (page_row, page_col),
+                grid: (row_starts.len(), col_starts.len()),
+                rows: page_rows,
+            });
+        }
+    }
+    Ok(pages)
+}
+
+// Renders the pattern as a staggered grid of square cells, odd rows (even ones when `flipped`)
+// shifted right by half a cell like the TUI, with each cell's one-char descriptor drawn in its
+// center.
+//
+// With `label_every`, every nth row is numbered on the left, every nth column is numbered above
+// the first row and below the last (each following that row's stagger), and the cells of those
+// rows and columns are outlined in GRID_COLOR. Numbers are 1-based like the rest of the UI.
+//
+// With `page`, `rows` are that page's rows: labels count from where the page sits in the whole
+// pattern, and its header is drawn across the top.
+pub fn export_image(
+    rows: &[Vec<Rgb8>],
+    color_map: &ColorMap,
+    path: impl AsRef<Path>,
+    cell_size: u32,
+    label_every: Option<NonZeroU32>,
+    flipped: bool,
+    page: Option<&Page>,
+) -> Result<(), Box<dyn Error>> {
+    if cell_size < MIN_CELL_SIZE {
+        return Err(format!("Cell size must be at least {} pixels", MIN_CELL_SIZE).into());
+    }
+    let longest = rows.iter().map(|r| r.len()).max().unwrap_or(0) as u32;
+    let grid_width = longest * cell_size + cell_size / 2 + 1;
+    let grid_height = rows.len() as u32 * cell_size + 1;
+
+    // Leave room for a 1px outline plus at least a pixel of padding around the glyph.
+    let scale = ((cell_size - 3) / font::GLYPH_HEIGHT).max(1);
+    let label_scale = (scale / 2).max(1);
+    let (first_row, first_col) = page.map_or((0, 0), |page| (page.top, page.left));
+    let (left_margin, top_margin) = match label_every {
+        Some(_) => (
+            font::text_size(&(first_row + rows.len()).to_string(), label_scale).0 + 2 * LABEL_PADDING,
+            font::GLYPH_HEIGHT * label_scale + 2 * LABEL_PADDING,
+        ),
+        None => (0, 0),
+    };
+    let header_height = match page {
+        Some(_) => font::GLYPH_HEIGHT * label_scale + 2 * LABEL_PADDING,
+        None => 0,
+    };
+    let mut img = RgbImage::from_pixel(
+        grid_width + left_margin,
+        header_height + grid_height + 2 * top_margin,
+        Rgb(SEPARATOR_COLOR.0),
+    );
+    if let Some(page) = page {
+        let header_pos = (LABEL_PADDING, LABEL_PADDING);
+        font::draw_text(&mut img, &page.header(), header_pos, label_scale, LABEL_COLOR);
+    }
+    let grid_y = header_height + top_margin;
+
+    let is_labeled = |idx: usize| label_every.is_some_and(|n| (idx as u32 + 1).is_multiple_of(n.get()));
+    let stagger = |row_idx: usize| if is_staggered(row_idx, flipped) { cell_size / 2 } else { 0 };
+    for (row_idx, row) in rows.iter().enumerate() {
+        for (col_idx, color) in row.iter().enumerate() {
+            let x0 = left_margin + col_idx as u32 * cell_size + stagger(row_idx);
+            let y0 = grid_y + row_idx as u32 * cell_size;
+            if is_labeled(first_row + row_idx) || is_labeled(first_col + col_idx) {
+                for y in y0..=(y0 + cell_size) {
+                    for x in x0..=(x0 + cell_size) {
+                        img[(x, y)] = GRID_COLOR;
+                    }
+                }
+            }
+            for y in (y0 + 1)..(y0 + cell_size) {
+                for x in (x0 + 1)..(x0 + cell_size) {
+                    img[(x, y)] = Rgb(color.0);
+                }
+            }
+
+            let label = color_map.one_char(*color).unwrap_or(UNMAPPED_CHAR);
+            let (text_width, text_height) = font::text_size(label, scale);
+            let text_x = x0 + 1 + (cell_size - 1).saturating_sub(text_width) / 2;
+            let text_y = y0 + 1 + (cell_size - 1).saturating_sub(text_height) / 2;
+            font::draw_text(&mut img, label, (text_x, text_y), scale, contrasting_text(*color));
+        }
+    }
+
+    if label_every.is_some() {
+        let label_height = font::GLYPH_HEIGHT * label_scale;
+        for row_idx in (0..rows.len()).filter(|idx| is_labeled(first_row + *idx)) {
+            let label = (first_row + row_idx + 1).to_string();
+            let (text_width, _) = font::text_size(&label, label_scale);
+            let x = left_margin - LABEL_PADDING - text_width;
+            let y = grid_y + row_idx as u32 * cell_size + (cell_size - label_height) / 2;
+            font::draw_text(&mut img, &label, (x, y), label_scale, LABEL_COLOR);
+        }
+        // Above the first row and below the last, so the columns can be found from either end.
+        let edges = [
+            (0, header_height + LABEL_PADDING),
+            (rows.len() - 1, grid_y + grid_height + LABEL_PADDING),
+        ];
+        for (row_idx, y) in edges {
+            for col_idx in (0..rows[row_idx].len()).filter(|idx| is_labeled(first_col + *idx)) {
+                let label = (first_col + col_idx + 1).to_string();
+                let (text_width, _) = font::text_size(&label, label_scale);
+                let center = left_margin + col_idx as u32 * cell_size + stagger(row_idx) + cell_size / 2;
+                let x = center.saturating_sub(text_width / 2);
+                font::draw_text(&mut img, &label, (x, y), label_scale, LABEL_COLOR);
+            }
+        }
+    }
+
+    img.save(path)?;
+    Ok(())
+}
+
+// Renders the pattern as staggered pointy-top hexagons, `hex_size` wide from flat side to flat
+// side, each labeled with its one-char descriptor. Unlike the PNG this scales to any print size.
+// Rows are staggered like the PNG's. With `page`, its header is written across the top.
+pub fn export_svg(
+    rows: &[Vec<Rgb8>],
+    color_map: &ColorMap,
+    hex_size: f64,
+    flipped: bool,
+    page: Option<&Page>,
+) -> String {
+    let radius = hex_size / 3f64.sqrt();
+    let row_step = radius * 1.5;
+    let header_height = if page.is_some() { hex_size } else { 0.0 };
+    let longest = rows.iter().map(|r| r.len()).max().unwrap_or(0) as f64;
+    let width = longest * hex_size + hex_size / 2.0;
+    let height = header_height + rows.len().saturating_sub(1) as f64 * row_step + 2.0 * radius;
+
+    let mut svg = String::new();
+    let _ = writeln!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 {:.2} {:.2}" width="{:.2}" height="{:.2}">"#,
+        width, height, width, height
+    );
+    let _ = writeln!(
+        svg,
+        r#"<rect width="100%" height="100%" fill="{}"/>"#,
+        SEPARATOR_COLOR.to_hex()
+    );
+    if let Some(page) = page {
+        let _ = writeln!(
+            svg,
+            r#"<text x="{:.2}" y="{:.2}" font-family="monospace" font-size="{:.2}" dominant-baseline="central" fill="{}">{}</text>"#,
+            hex_size / 4.0,
+            header_height / 2.0,
+            hex_size / 2.0,
+            Rgb8(LABEL_COLOR.0).to_hex(),
+            escape_xml(&page.header())
+        );
+    }
+    let _ = writeln!(
+        svg,
+        r#"<g font-family="monospace" font-size="{:.2}" text-anchor="middle" dominant-baseline="central">"#,
+        hex_size / 2.0
+    );
+    for (row_idx, row) in rows.iter().enumerate() {
+        let stagger = if is_staggered(row_idx, flipped) { hex_size / 2.0 } else { 0.0 };
+        let cy = header_height + radius + row_idx as f64 * row_step;
+        for (col_idx, color) in row.iter().enumerate() {
+            let cx = stagger + col_idx as f64 * hex_size + hex_size / 2.0;
+            // Corners clockwise from the top.
+            let points = (0..6)
+                .map(|i| {
+                    let angle = (60.0 * i as f64 - 90.0).to_radians();
+                    format!("{:.2},{:.2}", cx + radius * angle.cos(), cy + radius * angle.sin())
+                })
+                .collect::<Vec<_>>()
+                .join(" ");
+            let label = color_map.one_char(*color).unwrap_or(UNMAPPED_CHAR);
+            let _ = writeln!(
+                svg,
+                r#"<polygon points="{}" fill="{}" stroke="{}" stroke-width="1"/><text x="{:.2}" y="{:.2}" fill="{}">{}</text>"#,
+                points,
+                color.to_hex(),
+                SEPARATOR_COLOR.to_hex(),
+                cx,
+                cy,
+                Rgb8(contrasting_text(*color).0).to_hex(),
+                escape_xml(label)
+            );
+        }
+    }
+    svg.push_str("</g>\n</svg>\n");
+    svg
+}
+
+fn escape_xml(s: &str) -> String {
+    s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+// Black text on light cells, white text on dark ones.
+fn contrasting_text(color: Rgb8) -> Rgb<u8> {
+    let [r, g, b] = color.0.map(|c| c as u32);
+    let luma = (299 * r + 587 * g + 114 * b) / 1000;
+    if luma > 128 {
+        Rgb([0, 0, 0])
+    } else {
+        Rgb([255, 255, 255])
+    }
+}
+
+// Cell size of SVG charts. Like the hexagon SVG, they scale to any print size.
+const CHART_CELL: f64 = 20.0;
+// Columns and rows between the heavy grid lines of a chart, as on printed cross-stitch charts.
+const CHART_GRID_EVERY: usize = 10;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ChartFormat {
+    Text,
+    Svg,
+}
+
+impl ChartFormat {
+    // SVG for `.svg` paths, plain text for anything else.
+    pub fn from_path(path: &Path) -> ChartFormat {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("svg") => ChartFormat::Svg,
+            _ => ChartFormat::Text,
+        }
+    }
+}
+
+// One legend line: a color, its name and symbol, and how many links use it.
+struct LegendEntry {
+    color: Rgb8,
+    full_name: String,
+    symbol: String,
+    count: usize,
+}
+
+// Every color in `rows`, sorted by name like the color list.
+fn legend(rows: &[Vec<Rgb8>], color_map: &ColorMap) -> Vec<LegendEntry> {
+    let mut counts: HashMap<Rgb8, usize> = HashMap::new();
+    for color in rows.iter().flatten() {
+        *counts.entry(*color).or_default() += 1;
+    }
+    let mut entries: Vec<LegendEntry> = counts
+        .into_iter()
+        .map(|(color, count)| LegendEntry {
+            color,
+            full_name: color_map.full_name(color).unwrap_or(UNMAPPED_NAME).to_owned(),
+            symbol: color_map.one_char(color).unwrap_or(UNMAPPED_CHAR).to_owned(),
+            count,
+        })
+        .collect();
+    entries.sort_by(|a, b| a.full_name.cmp(&b.full_name).then(a.color.0.cmp(&b.color.0)));
+    entries
+}
+
+// Writes the pattern as a cross-stitch style chart: an unstaggered grid of square cells holding
+// each link's one-char descriptor, rows numbered on the left, every 10th column numbered above,
+// and a legend of symbol, color, name and link count below. Names are whatever the color map
+// holds, so naming colors by thread code (e.g. "DMC 321") puts the codes in the legend.
+pub fn export_chart(
+    rows: &[Vec<Rgb8>],
+    color_map: &ColorMap,
+    path: impl AsRef<Path>,
+    format: ChartFormat,
+) -> Result<(), Box<dyn Error>> {
+    std::fs::write(path, render_chart(rows, color_map, format))?;
+    Ok(())
+}
+
+pub fn render_chart(rows: &[Vec<Rgb8>], color_map: &ColorMap, format: ChartFormat) -> String {
+    match format {
+        ChartFormat::Text => text_chart(rows, color_map),
+        ChartFormat::Svg => svg_chart(rows, color_map),
+    }
+}
+
+fn text_chart(rows: &[Vec<Rgb8>], color_map: &ColorMap) -> String {
+    let longest = rows.iter().map(|r| r.len()).max().unwrap_or(0);
+    let gutter = rows.len().to_string().len() + 1;
+    let mut chart = String::new();
+
+    let mut ruler = vec![' '; gutter + longest * 2];
+    for col in (0..longest).filter(|col| *col == 0 || (col + 1) % CHART_GRID_EVERY == 0) {
+        for (slot, digit) in ruler[gutter + col * 2..].iter_mut().zip((col + 1).to_string().chars()) {
+            *slot = digit;
+        }
+    }
+    let _ = writeln!(chart, "{}", ruler.into_iter().collect::<String>().trim_end());
+    for (row_idx, row) in rows.iter().enumerate() {
+        let symbols = row
+            .iter()
+            .map(|color| color_map.one_char(*color).unwrap_or(UNMAPPED_CHAR))
+            .collect::<Vec<_>>()
+            .join(" ");
+        let _ = writeln!(chart, "{:>width$} {}", row_idx + 1, symbols, width = gutter - 1);
+    }
+
+    let entries = legend(rows, color_map);
+    let name_width = entries.iter().map(|e| e.full_name.chars().count()).max().unwrap_or(0);
+    let _ = writeln!(chart, "\nLegend:");
+    for entry in entries {
+        let _ = writeln!(
+            chart,
+            "  {}  {}  {:<width$}  {}",
+            entry.symbol,
+            entry.color.to_hex(),
+            entry.full_name,
+            entry.count,
+            width = name_width
+        );
+    }
+    chart
+}
+
+fn svg_chart(rows: &[Vec<Rgb8>], color_map: &ColorMap) -> String {
+    let cell = CHART_CELL;
+    let longest = rows.iter().map(|r| r.len()).max().unwrap_or(0);
+    let entries = legend(rows, color_map);
+    let (left, top) = (cell * 2.0, cell);
+    let grid_width = longest as f64 * cell;
+    let grid_height = rows.len() as f64 * cell;
+    let legend_top = top + grid_height + cell;
+    // Legend text is roughly half a cell per character at this font size.
+    let legend_width = entries
+        .iter()
+        .map(|e| cell * 1.5 + (e.full_name.chars().count() + 20) as f64 * cell / 2.0)
+        .fold(0.0, f64::max);
+    let width = left + grid_width.max(legend_width) + cell;
+    let height = legend_top + entries.len() as f64 * cell + cell;
+
+    let mut svg = String::new();
+    let _ = writeln!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 {:.2} {:.2}" width="{:.2}" height="{:.2}">"#,
+        width, height, width, height
+    );
+    let _ = writeln!(svg, r##"<rect width="100%" height="100%" fill="#FFFFFF"/>"##);
+    let _ = writeln!(
+        svg,
+        r#"<g font-family="monospace" font-size="{:.2}" text-anchor="middle" dominant-baseline="central">"#,
+        cell / 2.0
+    );
+    for (row_idx, row) in rows.iter().enumerate() {
+        let y = top + row_idx as f64 * cell;
+        let _ = writeln!(svg, r#"<text x="{:.2}" y="{:.2}">{}</text>"#, left - cell / 2.0, y + cell / 2.0, row_idx + 1);
+        for (col_idx, color) in row.iter().enumerate() {
+            let x = left + col_idx as f64 * cell;
+            let symbol = color_map.one_char(*color).unwrap_or(UNMAPPED_CHAR);
+            let _ = writeln!(
+                svg,
+                r##"<rect x="{:.2}" y="{:.2}" width="{:.2}" height="{:.2}" fill="{}" stroke="#808080" stroke-width="0.5"/><text x="{:.2}" y="{:.2}" fill="{}">{}</text>"##,
+                x,
+                y,
+                cell,
+                cell,
+                color.to_hex(),
+                x + cell / 2.0,
+                y + cell / 2.0,
+                Rgb8(contrasting_text(*color).0).to_hex(),
+                escape_xml(symbol)
+            );
+        }
+    }
+    for col in (0..longest).filter(|col| *col == 0 || (col + 1) % CHART_GRID_EVERY == 0) {
+        let _ = writeln!(
+            svg,
+            r#"<text x="{:.2}" y="{:.2}">{}</text>"#,
+            left + col as f64 * cell + cell / 2.0,
+            top / 2.0,
+            col + 1
+        );
+    }
+    // Heavy lines every CHART_GRID_EVERY cells and around the edge.
+    let heavy_line = |svg: &mut String, (x1, y1): (f64, f64), (x2, y2): (f64, f64)| {
+        let _ = writeln!(
+            svg,
+            r##"<line x1="{:.2}" y1="{:.2}" x2="{:.2}" y2="{:.2}" stroke="#000000" stroke-width="1.5"/>"##,
+            x1, y1, x2, y2
+        );
+    };
+    for col in (0..=longest).filter(|col| col % CHART_GRID_EVERY == 0 || *col == longest) {
+        let x = left + col as f64 * cell;
+        heavy_line(&mut svg, (x, top), (x, top + grid_height));
+    }
+    for row in (0..=rows.len()).filter(|row| row % CHART_GRID_EVERY == 0 || *row == rows.len()) {
+        let y = top + row as f64 * cell;
+        heavy_line(&mut svg, (left, y), (left + grid_width, y));
+    }
+    svg.push_str("</g>\n");
+
+    let _ = writeln!(
+        svg,
+        r#"<g font-family="monospace" font-size="{:.2}" dominant-baseline="central">"#,
+        cell / 2.0
+    );
+    for (idx, entry) in entries.iter().enumerate() {
+        let y = legend_top + idx as f64 * cell;
+        let _ = writeln!(
+            svg,
+            r##"<rect x="{:.2}" y="{:.2}" width="{:.2}" height="{:.2}" fill="{}" stroke="#808080" stroke-width="0.5"/><text x="{:.2}" y="{:.2}" text-anchor="middle" fill="{}">{}</text><text x="{:.2}" y="{:.2}">{}  {}  {}</text>"##,
+            left,
+            y,
+            cell,
+            cell,
+            entry.color.to_hex(),
+            left + cell / 2.0,
+            y + cell / 2.0,
+            Rgb8(contrasting_text(entry.color).0).to_hex(),
+            escape_xml(&entry.symbol),
+            left + cell * 1.5,
+            y + cell / 2.0,
+            entry.color.to_hex(),
+            escape_xml(&entry.full_name),
+            entry.count
+        );
+    }
+    svg.push_str("</g>\n</svg>\n");
+    svg
+}
@@ -1,24 +1,76 @@
+mod export;
+mod font;
+
+use clap::{Parser, ValueEnum};
 use itertools::Itertools;
 use crossterm::{
-    event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEventKind, KeyModifiers},
+    event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEventKind},
     execute,
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
 use directories::ProjectDirs;
 use image::{io::Reader as ImageReader, Rgb, RgbImage};
-use ratatui::{prelude::*, symbols::scrollbar, widgets::*};
+use ratatui::{prelude::*, widgets::*};
+use rayon::prelude::*;
 use serde::{Deserialize, Serialize};
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet, VecDeque},
     error::Error,
     ffi::OsStr,
-    fs, io,
+    num::{NonZeroU32, NonZeroUsize},
+    fs,
+    io::{self, IsTerminal},
     path::{Path, PathBuf},
     time::{Duration, Instant},
 };
 
 // The "Outline" color. Default is this.
 const SEPARATOR_COLOR: Rgb8 = Rgb8([32, 32, 32]);
+// Shown in place of a name or description for colors missing from the color map.
+const UNMAPPED_NAME: &str = "Unnamed";
+const UNMAPPED_CHAR: &str = "?";
+// Paints detected outline pixels in the source preview's outline overlay.
+const OUTLINE_OVERLAY_COLOR: Rgb8 = Rgb8([255, 0, 255]);
+// How many recent ticks are used to estimate the weaving rate.
+const RATE_WINDOW: usize = 30;
+// A gap between ticks longer than this is treated as a break and restarts the rate estimate.
+const RATE_RESET_PAUSE: Duration = Duration::from_secs(5 * 60);
+// Row lengths whose standard deviation exceeds this fraction of the mean likely mean a bad parse.
+const ROW_LENGTH_WARN_RATIO: f64 = 0.25;
+// Links filling more than this many times the median region size were probably merged with a
+// neighbour through a gap in the outline.
+const REGION_SIZE_OUTLIER_RATIO: usize = 3;
+
+// Pixel count of each link's region, laid out like the rows.
+type RegionSizes = Vec<Vec<usize>>;
+// Decides whether a pixel color is part of the outline between links.
+type SeparatorPredicate = Box<dyn Fn(Rgb8) -> bool + Send + Sync>;
+// How many color map edits can be undone.
+const COLOR_HISTORY_DEPTH: usize = 100;
+// Width of each link in the pattern pane when showing full color names.
+const NAME_CELL_WIDTH: usize = 8;
+// Columns left of each pattern row for its done checkmark.
+const ROW_GUTTER_WIDTH: usize = 2;
+// Below this the layout has no room for the pattern pane's border and a line of links, so ui
+// asks for a bigger terminal instead.
+const MIN_TERMINAL_SIZE: (u16, u16) = (40, 15);
+// Bump whenever a field is added to Config or Progress, and add a step to Config::migrate if the
+// serde default isn't the right value for older files.
+const CONFIG_VERSION: u32 = 15;
+// Compared against RowBuilder::estimate_links, which overcounts a few times per link. Leaves room
+// for large patterns while still catching a photo opened by mistake.
+const DEFAULT_MAX_LINKS: usize = 250_000;
+// Largest region a single link's flood fill may cover. Far above any real link, so a fill that
+// goes past it has most likely leaked through a gap in the outline.
+const DEFAULT_MAX_REGION: usize = 20_000;
+// New colors this close to a named one, on every channel, are offered that color's name.
+const DEFAULT_SUGGEST_DISTANCE: u8 = 16;
+// How many rows are woven side by side at the start of a pattern, unless configured otherwise.
+const DEFAULT_SEED_ROWS: usize = 3;
+// The TUI saves after this many links by default, so a crash doesn't cost a whole session.
+const DEFAULT_AUTOSAVE_EVERY: usize = 10;
+// Unsaved changes older than this are saved even if fewer links were woven.
+const AUTOSAVE_INTERVAL: Duration = Duration::from_secs(30);
 
 fn rgb8_to_true(rgb: Rgb8) -> colored::Color {
     colored::Color::TrueColor {
@@ -28,8 +80,176 @@ fn rgb8_to_true(rgb: Rgb8) -> colored::Color {
     }
 }
 
+#[derive(Parser)]
+#[command(version, about = "Walks through a chainmail pattern image one link at a time")]
+struct Cli {
+    /// The pattern image to open
+    #[arg(required_unless_present = "config")]
+    file: Option<PathBuf>,
+    /// Print or --export-palette the color map of this saved config, without opening an image
+    #[arg(long, value_name = "PATH", conflicts_with = "file")]
+    config: Option<PathBuf>,
+    /// Which neighbors a link's flood fill spreads to
+    #[arg(long, value_enum, default_value_t)]
+    connectivity: Connectivity,
+    /// Whether each blob of color is a link, or each pixel is, for images without outlines
+    #[arg(long, value_enum, default_value_t)]
+    pixel_mode: PixelMode,
+    /// Render the pattern to a PNG at this path and exit instead of opening the TUI
+    #[arg(long, value_name = "PATH")]
+    export_png: Option<PathBuf>,
+    /// Render the pattern to an SVG of hexagons at this path and exit
+    #[arg(long, value_name = "PATH")]
+    export_svg: Option<PathBuf>,
+    /// Write a cross-stitch style chart (symbol grid plus legend) to this path and exit. SVG for
+    /// `.svg` paths, plain text otherwise
+    #[arg(long, value_name = "PATH")]
+    export_chart: Option<PathBuf>,
+    /// Size in pixels of each link's cell in exported images
+    #[arg(long, value_name = "PIXELS", default_value_t = 24)]
+    cell_size: u32,
+    /// Split exports and printed patterns into pages of at most this many rows
+    #[arg(long, value_name = "ROWS")]
+    page_rows: Option<NonZeroUsize>,
+    /// Split exports and printed patterns into pages of at most this many links per row
+    #[arg(long, value_name = "LINKS")]
+    page_cols: Option<NonZeroUsize>,
+    /// Rows and links that neighboring pages share, to line printouts up
+    #[arg(long, value_name = "N", default_value_t = 1)]
+    page_overlap: usize,
+    /// When printing the pattern to a pipe, put the links already woven in brackets and mark rows
+    /// in progress with `>`
+    #[arg(long)]
+    annotate_progress: bool,
+    /// Limit exports and the printed pattern to the links the weave hasn't reached yet, starting
+    /// partway through the current row. Starts on an even row so odd rows keep their stagger
+    #[arg(long, conflicts_with = "annotate_progress")]
+    remaining: bool,
+    /// Number and outline every Nth row and column in exported images
+    #[arg(long, value_name = "N")]
+    grid_labels: Option<NonZeroU32>,
+    /// Write the named colors as a GIMP palette (.gpl) to this path and exit
+    #[arg(long, value_name = "PATH")]
+    export_palette: Option<PathBuf>,
+    /// Refuse images that look like they have more links than this, e.g. photos
+    #[arg(long, value_name = "N", default_value_t = DEFAULT_MAX_LINKS)]
+    max_links: usize,
+    /// Give up when a single link covers more than this many pixels, e.g. through a gap in the outline
+    #[arg(long, value_name = "PIXELS", default_value_t = DEFAULT_MAX_REGION)]
+    max_region: usize,
+    /// Crop away outline-colored borders before finding links, e.g. on a padded screenshot
+    #[arg(long)]
+    autocrop: bool,
+    /// When naming a new color, offer to reuse the name of a named color whose channels all differ
+    /// by at most this much. 0 turns the suggestion off
+    #[arg(long, value_name = "DIST", default_value_t = DEFAULT_SUGGEST_DISTANCE)]
+    suggest_distance: u8,
+    /// Merge colors whose channels all differ by at most this much into one color
+    #[arg(long, value_name = "DIST")]
+    merge_colors: Option<u8>,
+    /// Check that the image parses and every color is named, then exit without prompting
+    #[arg(long)]
+    check: bool,
+    /// List colors in the image that have no name and names for colors no longer in it, then exit
+    #[arg(long)]
+    verify: bool,
+    /// Print a one-line summary of the saved progress and exit, e.g. for a status bar
+    #[arg(long)]
+    status: bool,
+    /// Compare the pattern against another image and print the links that differ
+    #[arg(long, value_name = "OTHER_IMAGE")]
+    diff: Option<PathBuf>,
+    /// Print how many pixels each link covered and flag suspiciously large ones
+    #[arg(long)]
+    region_stats: bool,
+    /// Re-chunk the links into rows of this many links, e.g. when weaving two grid rows as one
+    #[arg(long, value_name = "WIDTH")]
+    reflow: Option<NonZeroUsize>,
+    /// Start the TUI showing a preview of the source image instead of the pattern
+    #[arg(long)]
+    show_image: bool,
+    /// Keep a <pattern>.progress.json with the live position and rate next to the config, for overlays
+    #[arg(long)]
+    emit_progress_json: bool,
+    /// Milliseconds between links while auto-advancing
+    #[arg(long, value_name = "MS", default_value_t = 1000)]
+    play_interval: u64,
+    /// Use the color of the pixel at X,Y as the outline color
+    #[arg(long, value_name = "X,Y", value_parser = parse_coords)]
+    separator_at: Option<(u32, u32)>,
+    /// Treat this color as an outline (repeatable). Replaces the saved outline colors
+    #[arg(long = "separator", value_name = "HEX", value_parser = Rgb8::from_hex)]
+    separators: Vec<Rgb8>,
+    /// Treat this color as canvas that isn't woven (repeatable). Replaces the saved background colors
+    #[arg(long = "background", value_name = "HEX", value_parser = Rgb8::from_hex)]
+    background_colors: Vec<Rgb8>,
+    /// Reverse every row to weave right to left. Saved for next time; toggle in the TUI with M
+    #[arg(long)]
+    mirror: bool,
+    /// How many rows are woven together at the start of the pattern. Saved for next time
+    #[arg(long, value_name = "N")]
+    seed_rows: Option<NonZeroUsize>,
+    /// Write a copy of the color map and progress to this path and exit
+    #[arg(long, value_name = "PATH")]
+    save_config: Option<PathBuf>,
+    /// Open the pattern even if another instance seems to have it open, e.g. after a crash
+    #[arg(long)]
+    force: bool,
+}
+
+fn parse_coords(s: &str) -> Result<(u32, u32), String> {
+    let (x, y) = s.split_once(',').ok_or("expected coordinates as X,Y")?;
+    let parse = |v: &str| v.trim().parse::<u32>().map_err(|e| e.to_string());
+    Ok((parse(x)?, parse(y)?))
+}
+
+// Which neighboring pixels count as part of the same link when flood filling.
+//
+// With `Four`, only the pixels directly above/below/left/right are joined, so a link may touch
+// the outline diagonally without leaking through it. With `Eight`, diagonal neighbors are joined
+// as well: links drawn with thin diagonal strokes stay in one piece, but the separator outline
+// must then be at least two pixels thick on diagonals, or neighboring links will be merged.
+#[derive(ValueEnum, Default, Clone, Copy, PartialEq, Eq, Debug)]
+enum Connectivity {
+    #[default]
+    Four,
+    Eight,
+}
+
+// How the image's pixels map to links. `Blob` flood fills each outlined blob into one link.
+// `PerPixel` is for outline-less images where every pixel is a link of its own, and skips the
+// flood fill entirely.
+#[derive(ValueEnum, Default, Clone, Copy, PartialEq, Eq, Debug)]
+enum PixelMode {
+    #[default]
+    Blob,
+    PerPixel,
+}
+
 #[derive(Serialize, Deserialize, Hash, Eq, PartialEq, PartialOrd, Clone, Copy, Debug)]
 struct Rgb8([u8; 3]);
+impl Rgb8 {
+    fn to_hex(self) -> String {
+        format!("#{:02X}{:02X}{:02X}", self.0[0], self.0[1], self.0[2])
+    }
+
+    // The largest difference on any one channel.
+    fn distance(self, other: Rgb8) -> u8 {
+        self.0.iter().zip(other.0.iter()).map(|(a, b)| a.abs_diff(*b)).max().unwrap_or(0)
+    }
+
+    // Parses `RRGGBB`, with or without a leading `#`.
+    fn from_hex(s: &str) -> Result<Rgb8, String> {
+        let digits = s.strip_prefix('#').unwrap_or(s);
+        if digits.len() != 6 || !digits.is_ascii() {
+            return Err(format!("expected a hex color like #202020, got {:?}", s));
+        }
+        let channel = |idx: usize| {
+            u8::from_str_radix(&digits[idx * 2..idx * 2 + 2], 16).map_err(|e| e.to_string())
+        };
+        Ok(Rgb8([channel(0)?, channel(1)?, channel(2)?]))
+    }
+}
 trait ToRgb8 {
     fn to_rgb8(self) -> Rgb8;
 }
@@ -43,6 +263,30 @@ impl ToRgb8 for Rgb<u8> {
 struct ColorMap {
     full_names: HashMap<Rgb8, String>,
     short_char: HashMap<Rgb8, String>,
+    // Only kept for the current run.
+    #[serde(skip)]
+    history: ColorHistory,
+    // How close a new color must be to a named one for ensure_mapped to suggest reusing its name.
+    #[serde(skip, default = "default_suggest_distance")]
+    suggest_distance: u8,
+}
+
+fn default_suggest_distance() -> u8 {
+    DEFAULT_SUGGEST_DISTANCE
+}
+
+// A color's (full name, one char) before and after an edit. `None` means it was unmapped.
+#[derive(Debug)]
+struct ColorEdit {
+    color: Rgb8,
+    before: Option<(String, String)>,
+    after: Option<(String, String)>,
+}
+
+#[derive(Default, Debug)]
+struct ColorHistory {
+    undo: VecDeque<ColorEdit>,
+    redo: Vec<ColorEdit>,
 }
 
 impl ColorMap {
@@ -50,6 +294,8 @@ impl ColorMap {
         ColorMap {
             full_names: HashMap::new(),
             short_char: HashMap::new(),
+            history: ColorHistory::default(),
+            suggest_distance: DEFAULT_SUGGEST_DISTANCE,
         }
     }
 
@@ -57,58 +303,356 @@ impl ColorMap {
         use colored::Colorize;
         use io::Write;
 
-        if self.full_names.contains_key(&color) {
+        if self.is_mapped(color) {
             return Ok(());
         }
         let colored_rgb = format!("{:?}", color)
             .color(rgb8_to_true(color))
             .on_color(rgb8_to_true(SEPARATOR_COLOR));
-        println!("Found new color: {}", colored_rgb);
-        print!("Please give it a name: ");
-        io::stdout().flush()?;
+        eprintln!("Found new color: {}", colored_rgb);
+        if let Some((near, full_name, one_char)) = self
+            .nearest_entry(color)
+            .filter(|(near, _, _)| near.distance(color) <= self.suggest_distance)
+        {
+            eprint!("Close to '{}' {} - reuse its name? [Y/n] ", full_name, near.to_hex());
+            io::stderr().flush()?;
+            let mut answer = String::new();
+            io::stdin().read_line(&mut answer)?;
+            if !answer.trim().to_lowercase().starts_with('n') {
+                let (full_name, one_char) = (full_name.to_owned(), one_char.to_owned());
+                self.add_entry(color, &full_name, &one_char);
+                return Ok(());
+            }
+        }
+        eprint!("Please give it a name: ");
+        io::stderr().flush()?;
         let mut name = String::new();
         io::stdin().read_line(&mut name)?;
-        self.full_names.insert(color, name.trim().to_owned());
-        print!("Please give it a 1 character description: ");
-        io::stdout().flush()?;
-        io::stdin().read_line(&mut name)?;
-        self.short_char
-            .insert(color, name.trim().chars().nth(0).unwrap().to_string());
+        eprint!("Please give it a 1 character description: ");
+        io::stderr().flush()?;
+        let mut one_char = String::new();
+        io::stdin().read_line(&mut one_char)?;
+        let one_char = one_char
+            .trim()
+            .chars()
+            .next()
+            .or_else(|| name.trim().chars().next())
+            .ok_or("A color needs a name or a description")?;
+        self.add_entry(color, name.trim(), &one_char.to_string());
+        Ok(())
+    }
+
+    // The named color closest to `color`, ties going to the lowest RGB value.
+    fn nearest_entry(&self, color: Rgb8) -> Option<(Rgb8, &str, &str)> {
+        self.entries().min_by_key(|(named, _, _)| (named.distance(color), named.0))
+    }
+
+    fn is_mapped(&self, color: Rgb8) -> bool {
+        self.full_names.contains_key(&color)
+    }
+
+    // How many of `colors` would be prompted for by `ensure_mapped`.
+    fn count_unmapped(&self, colors: &[Rgb8]) -> usize {
+        colors.iter().filter(|color| !self.is_mapped(**color)).count()
+    }
+
+    // Names a new color or renames a mapped one, as one undoable edit.
+    fn add_entry(&mut self, color: Rgb8, full_name: &str, one_char: &str) {
+        self.apply_edit(color, Some((full_name.to_owned(), one_char.to_owned())));
+    }
+
+    fn undo(&mut self) -> bool {
+        let Some(edit) = self.history.undo.pop_back() else {
+            return false;
+        };
+        self.set_entry(edit.color, edit.before.clone());
+        self.history.redo.push(edit);
+        true
+    }
+
+    fn redo(&mut self) -> bool {
+        let Some(edit) = self.history.redo.pop() else {
+            return false;
+        };
+        self.set_entry(edit.color, edit.after.clone());
+        self.history.undo.push_back(edit);
+        true
+    }
+
+    fn apply_edit(&mut self, color: Rgb8, after: Option<(String, String)>) {
+        let before = self.entry(color);
+        self.set_entry(color, after.clone());
+        if self.history.undo.len() == COLOR_HISTORY_DEPTH {
+            self.history.undo.pop_front();
+        }
+        self.history.undo.push_back(ColorEdit { color, before, after });
+        self.history.redo.clear();
+    }
+
+    fn entry(&self, color: Rgb8) -> Option<(String, String)> {
+        let full_name = self.full_names.get(&color)?;
+        let one_char = self.short_char.get(&color)?;
+        Some((full_name.clone(), one_char.clone()))
+    }
+
+    fn set_entry(&mut self, color: Rgb8, entry: Option<(String, String)>) {
+        match entry {
+            Some((full_name, one_char)) => {
+                self.full_names.insert(color, full_name);
+                self.short_char.insert(color, one_char);
+            }
+            None => {
+                self.full_names.remove(&color);
+                self.short_char.remove(&color);
+            }
+        }
+    }
+
+    // Every mapped color with its full name and one-char description, in no particular order.
+    fn entries(&self) -> impl Iterator<Item = (Rgb8, &str, &str)> {
+        self.full_names.iter().filter_map(|(color, full_name)| {
+            let one_char = self.short_char.get(color)?;
+            Some((*color, full_name.as_str(), one_char.as_str()))
+        })
+    }
+
+    // None for colors that aren't mapped, e.g. after a config was edited by hand.
+    fn full_name(&self, color: Rgb8) -> Option<&str> {
+        self.full_names.get(&color).map(String::as_str)
+    }
+
+    fn one_char(&self, color: Rgb8) -> Option<&str> {
+        self.short_char.get(&color).map(String::as_str)
+    }
+
+    // Writes the named colors as a GIMP palette, which Paint.NET and most other editors can also
+    // import. The palette is named after the file.
+    fn export_gpl(&self, path: &Path) -> Result<(), Box<dyn Error>> {
+        let name = path.file_stem().and_then(OsStr::to_str).unwrap_or("Pattern");
+        fs::write(path, self.to_gpl(name))?;
         Ok(())
     }
 
-    fn full_name(&self, color: Rgb8) -> &str {
-        &self.full_names[&color]
+    // Like `entries`, but sorted by name, then color.
+    fn sorted_entries(&self) -> Vec<(Rgb8, &str, &str)> {
+        let mut entries: Vec<_> = self.entries().collect();
+        entries.sort_by_key(|(color, full_name, _)| (*full_name, color.0));
+        entries
     }
 
-    fn one_char(&self, color: Rgb8) -> &str {
-        &self.short_char[&color]
+    // Swatches are sorted by name so the output doesn't depend on hash order.
+    fn to_gpl(&self, name: &str) -> String {
+        let entries = self.sorted_entries();
+        let mut gpl = format!("GIMP Palette\nName: {}\n#\n", name);
+        for (color, full_name, _) in entries {
+            let [r, g, b] = color.0;
+            gpl += &format!("# {}\n{:3} {:3} {:3}\t{}\n", color.to_hex(), r, g, b, full_name);
+        }
+        gpl
     }
 }
 
-#[derive(Serialize, Deserialize, Hash, Eq, PartialEq, PartialOrd, Clone, Debug)]
+#[derive(Serialize, Deserialize, Eq, PartialEq, Clone, Debug)]
 struct Progress {
     row: usize,
     col: usize,
+    // Individual links the user has checked off, as (row, col) into the pattern rows.
+    #[serde(default)]
+    completed: HashSet<(usize, usize)>,
+    // Rows the user has checked off as finished, indexed like the pattern rows. Rows past the end
+    // aren't done.
+    #[serde(default)]
+    rows_done: Vec<bool>,
 }
 impl Progress {
     fn new() -> Self {
-        Progress { row: 2, col: 1 }
+        Progress::at_start(DEFAULT_SEED_ROWS)
     }
-    fn reset(&mut self) {
-        self.row = 2;
-        self.col = 1;
+
+    // The position before anything is woven: on the last seed row, with the first link of each
+    // seed row revealed.
+    fn at_start(seed_rows: usize) -> Self {
+        Progress { row: seed_rows - 1, col: 1, completed: HashSet::new(), rows_done: vec![] }
+    }
+
+    fn reset(&mut self, seed_rows: usize) {
+        *self = Progress::at_start(seed_rows);
+    }
+
+    fn is_at_start(&self, seed_rows: usize) -> bool {
+        (self.row, self.col) == (seed_rows - 1, 1)
+    }
+
+    // Keeps the position inside `rows`, e.g. after the image was edited.
+    fn clamp_to(&mut self, rows: &[Vec<Rgb8>]) {
+        if rows.is_empty() {
+            return;
+        }
+        if self.row >= rows.len() {
+            self.row = rows.len() - 1;
+            self.col = rows[self.row].len();
+        }
+        self.col = self.col.min(rows[self.row].len());
+        self.completed
+            .retain(|(row, col)| rows.get(*row).is_some_and(|r| *col < r.len()));
+        self.rows_done.truncate(rows.len());
+    }
+
+    fn is_row_done(&self, row: usize) -> bool {
+        self.rows_done.get(row).copied().unwrap_or(false)
+    }
+
+    fn set_row_done(&mut self, row: usize, done: bool) {
+        if self.rows_done.len() <= row {
+            self.rows_done.resize(row + 1, false);
+        }
+        self.rows_done[row] = done;
     }
 }
 
 #[derive(Serialize, Deserialize)]
 struct Config {
+    // Files written before versioning have no version and load as 0.
+    #[serde(default)]
+    version: u32,
     config_path: PathBuf,
     color_map: ColorMap,
     progress: Progress,
+    // Stop and show a banner after each finished row so the user can attach it.
+    #[serde(default = "default_true")]
+    pause_on_row_complete: bool,
+    // Colors that outline links rather than being links themselves.
+    #[serde(default = "default_separators")]
+    separators: Vec<Rgb8>,
+    // When set, even rows are indented instead of odd ones.
+    #[serde(default)]
+    stagger_parity: bool,
+    // (vertical, horizontal) scroll of the pattern view when the TUI was last closed. Kept apart
+    // from `progress` so looking around doesn't move the weave position.
+    #[serde(default)]
+    scroll: Option<(usize, usize)>,
+    // Rows woven side by side at the start, before the pattern continues one row at a time.
+    #[serde(default = "default_seed_rows")]
+    seed_rows: usize,
+    // Links woven between autosaves in the TUI. 0 saves only on the time interval and on quit.
+    #[serde(default = "default_autosave_every")]
+    autosave_every: usize,
+    // Canvas colors that are neither links nor outlines. They are skipped instead of woven.
+    #[serde(default)]
+    background_colors: HashSet<Rgb8>,
+    // Each row is reversed, for weaving right to left. Progress counts links in the reversed order.
+    #[serde(default)]
+    mirror: bool,
+    // Named positions to jump back to, like vim marks. Only row and col are used.
+    #[serde(default)]
+    bookmarks: HashMap<String, Progress>,
+    // Check a row off automatically once its last link is woven.
+    #[serde(default)]
+    auto_mark_rows: bool,
+    // Ask before `q` leaves the TUI, so a stray keypress doesn't end the session.
+    #[serde(default = "default_true")]
+    confirm_quit: bool,
+    // Reminders attached to (row, col) links, shown when the weave reaches them.
+    #[serde(default)]
+    notes: HashMap<(usize, usize), String>,
+    // Key names (as from `key_name`) to what they do in the TUI. Keys left out do nothing.
+    #[serde(default = "default_keymap")]
+    keymap: HashMap<String, Action>,
+    // Colors the user already has enough of, left out of the color list's shopping total.
+    #[serde(default)]
+    completed_colors: HashSet<Rgb8>,
+    // The `--reflow` width that progress, notes and bookmarks were saved against. None for the
+    // rows as detected.
+    #[serde(default)]
+    reflow: Option<NonZeroUsize>,
+}
+
+// The parts of a config worth keeping when the rest of the file can't be read.
+#[derive(Deserialize)]
+struct SalvagedConfig {
+    #[serde(default = "ColorMap::new")]
+    color_map: ColorMap,
+    #[serde(default = "Progress::new")]
+    progress: Progress,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_separators() -> Vec<Rgb8> {
+    vec![SEPARATOR_COLOR]
+}
+
+fn default_seed_rows() -> usize {
+    DEFAULT_SEED_ROWS
+}
+
+fn default_autosave_every() -> usize {
+    DEFAULT_AUTOSAVE_EVERY
+}
+
+fn default_keymap() -> HashMap<String, Action> {
+    use Action::*;
+    [
+        ("q", Quit),
+        ("Space", Next),
+        ("P", SkipAhead),
+        ("a", AutoPlay),
+        ("Left", ScrollLeft),
+        ("h", ScrollLeft),
+        ("Down", ScrollDown),
+        ("j", ScrollDown),
+        ("Up", ScrollUp),
+        ("k", ScrollUp),
+        ("Right", ScrollRight),
+        ("l", ScrollRight),
+        ("g", ScrollTop),
+        ("G", ScrollBottom),
+        ("Home", ScrollRowStart),
+        ("End", ScrollRowEnd),
+        ("c", GoToCurrent),
+        ("f", FocusColor),
+        ("F", ClearFocus),
+        ("C", ColorList),
+        ("i", ToggleSourceImage),
+        ("o", ToggleOutlines),
+        ("v", ToggleSplit),
+        ("Tab", SwitchPane),
+        ("x", ToggleLinkDone),
+        ("X", ToggleRowDone),
+        ("b", TakeBreak),
+        ("R", ToggleRuns),
+        ("N", ToggleFullNames),
+        ("I", ToggleIndices),
+        ("m", SetBookmark),
+        ("'", JumpToBookmark),
+        ("J", JumpToRow),
+        ("s", ToggleStagger),
+        ("M", ToggleMirror),
+        ("e", EditColor),
+        ("n", EditNote),
+        ("u", Undo),
+        ("U", Redo),
+        ("r", Reset),
+    ]
+    .into_iter()
+    .map(|(key, action)| (key.to_owned(), action))
+    .collect()
 }
 
 impl Config {
+    // Reads a config file as is, e.g. one given on the command line. Unlike `load`, a missing or
+    // broken file is an error rather than a fresh or salvaged config.
+    fn from_file(path: &Path) -> Result<Config, Box<dyn Error>> {
+        let contents =
+            fs::read_to_string(path).map_err(|e| format!("Could not read config {}: {}", path.display(), e))?;
+        let mut config: Config =
+            ron::from_str(&contents).map_err(|e| format!("Could not parse config {}: {}", path.display(), e))?;
+        config.migrate();
+        Ok(config)
+    }
+
     fn load(
         project_dir: PathBuf,
         pattern_file: impl AsRef<Path>,
@@ -123,51 +667,235 @@ impl Config {
             fs::create_dir_all(project_dir)?;
         }
 
-        let mut config: Config = fs::read_to_string(&config_path)
-            .ok()
-            .and_then(|s| ron::from_str(&s).ok())
-            .unwrap_or(Config {
-                config_path: config_path.clone(),
-                color_map: ColorMap::new(),
-                progress: Progress::new(),
-            });
+        let mut config = match fs::read_to_string(&config_path) {
+            Ok(contents) => Config::parse_or_salvage(&contents, &config_path)?,
+            Err(_) => Config::new(config_path.clone()),
+        };
         config.config_path = config_path;
+        config.migrate();
 
         Ok(config)
     }
 
+    fn new(config_path: PathBuf) -> Config {
+        Config {
+            version: CONFIG_VERSION,
+            config_path,
+            color_map: ColorMap::new(),
+            progress: Progress::new(),
+            pause_on_row_complete: true,
+            separators: default_separators(),
+            stagger_parity: false,
+            scroll: None,
+            seed_rows: DEFAULT_SEED_ROWS,
+            autosave_every: DEFAULT_AUTOSAVE_EVERY,
+            background_colors: HashSet::new(),
+            mirror: false,
+            bookmarks: HashMap::new(),
+            auto_mark_rows: false,
+            confirm_quit: true,
+            notes: HashMap::new(),
+            keymap: default_keymap(),
+            completed_colors: HashSet::new(),
+            reflow: None,
+        }
+    }
+
+    // A file that doesn't parse as a whole (e.g. written by a newer or older version with an
+    // incompatible field) still shouldn't cost the user their named colors. Keep what can be read,
+    // and back up the original before it gets overwritten.
+    fn parse_or_salvage(contents: &str, config_path: &Path) -> Result<Config, Box<dyn Error>> {
+        let err = match ron::from_str::<Config>(contents) {
+            Ok(config) => return Ok(config),
+            Err(err) => err,
+        };
+        let mut backup_name = config_path.as_os_str().to_owned();
+        backup_name.push(".bak");
+        let backup_path = PathBuf::from(backup_name);
+        fs::copy(config_path, &backup_path)?;
+        eprintln!(
+            "Could not fully read {} ({}); a copy was saved to {}",
+            config_path.display(),
+            err,
+            backup_path.display()
+        );
+
+        let mut config = Config::new(config_path.to_owned());
+        config.version = 0;
+        if let Ok(salvaged) = ron::from_str::<SalvagedConfig>(contents) {
+            config.color_map = salvaged.color_map;
+            config.progress = salvaged.progress;
+        }
+        Ok(config)
+    }
+
+    // Brings a config written by an older version up to date.
+    fn migrate(&mut self) {
+        // What each version lacks, along with everything the versions after it lack. Fields get
+        // their serde defaults; keymap keys are added below unless the user bound them elsewhere.
+        // 0: separators, completed links, pause_on_row_complete, stagger_parity and scroll.
+        // 1: seed_rows, whose default is the count that used to be hardcoded.
+        // 2: autosave_every.
+        // 3: background_colors.
+        // 4: mirror.
+        // 5: bookmarks.
+        // 6: rows_done and auto_mark_rows.
+        // 7: confirm_quit.
+        // 8: notes.
+        // 9: keymap.
+        // 10: completed_colors.
+        // 11: the link indices key.
+        // 12: the jump-to-row key.
+        // 13: the outline overlay key.
+        // 14: reflow.
+        if self.version < 12 {
+            self.keymap.entry("I".to_owned()).or_insert(Action::ToggleIndices);
+        }
+        if self.version < 13 {
+            self.keymap.entry("J".to_owned()).or_insert(Action::JumpToRow);
+        }
+        if self.version < 14 {
+            self.keymap.entry("o".to_owned()).or_insert(Action::ToggleOutlines);
+        }
+        self.version = CONFIG_VERSION;
+    }
+
+    // A weave that hasn't started yet moves to the start for the new count, including a fresh
+    // config's, which starts at the default count.
+    fn set_seed_rows(&mut self, seed_rows: usize) {
+        if self.progress.is_at_start(self.seed_rows) {
+            (self.progress.row, self.progress.col) = (seed_rows - 1, 1);
+        }
+        self.seed_rows = seed_rows;
+    }
+
+    // After every row was flipped end to end, keeps notes on the same links, like checked-off ones,
+    // and bookmarks on the link that was current when they were set. Bookmarks among the seed rows,
+    // which are woven side by side, stay put.
+    fn mirror_links(&mut self, rows: &[Vec<Rgb8>]) {
+        self.notes = self
+            .notes
+            .drain()
+            .filter_map(|((row, col), note)| {
+                let len = rows.get(row)?.len();
+                Some(((row, len.checked_sub(col + 1)?), note))
+            })
+            .collect();
+        for mark in self.bookmarks.values_mut().filter(|mark| mark.row >= self.seed_rows) {
+            let Some(len) = rows.get(mark.row).map(Vec::len) else {
+                continue;
+            };
+            // The current link is the one before `col`, except at the start of a row.
+            mark.col = if mark.col == 0 { len } else { (len + 1).saturating_sub(mark.col) };
+        }
+    }
+
+    // Moves progress, notes and bookmarks from rows laid out as `from` onto the same links laid out
+    // as `to`. Positions keep their count of woven links, except in the seed phase, where the seed
+    // rows are woven side by side; checked-off links, finished rows and notes stay on their links.
+    fn move_to_layout(&mut self, from: &RowLayout, to: &RowLayout) {
+        let seed_rows = self.seed_rows;
+        let move_position = |progress: &mut Progress| {
+            if progress.row >= seed_rows {
+                (progress.row, progress.col) = to.position_after(from.woven_before((progress.row, progress.col)));
+            }
+        };
+        move_position(&mut self.progress);
+        self.bookmarks.values_mut().for_each(move_position);
+        let move_link = |link: (usize, usize)| to.link_at(from.link_index(link)?);
+        let progress = &mut self.progress;
+        progress.completed = progress.completed.iter().filter_map(|link| move_link(*link)).collect();
+        let done_links: Vec<bool> =
+            (0..to.starts[to.row_count()]).map(|index| progress.is_row_done(from.row_of(index).unwrap())).collect();
+        progress.rows_done = (0..to.row_count())
+            .map(|row| to.row_len(row) > 0 && done_links[to.starts[row]..to.starts[row + 1]].iter().all(|done| *done))
+            .collect();
+        self.notes = self.notes.drain().filter_map(|(link, note)| Some((move_link(link)?, note))).collect();
+    }
+
     fn save(&self) -> Result<(), Box<dyn Error>> {
-        fs::write(&self.config_path, ron::to_string(&self)?)?;
+        self.save_as(&self.config_path)
+    }
+
+    // `<pattern>.<extension>` next to the config file.
+    fn sibling_path(&self, extension: &str) -> PathBuf {
+        let config_name = self.config_path.file_name().unwrap().to_string_lossy();
+        let pattern_name = config_name.strip_suffix(".config.ron").unwrap_or(&config_name);
+        self.config_path.with_file_name(format!("{}.{}", pattern_name, extension))
+    }
+
+    fn progress_json_path(&self) -> PathBuf {
+        self.sibling_path("progress.json")
+    }
+
+    fn lock_path(&self) -> PathBuf {
+        self.sibling_path("lock")
+    }
+
+    // Writes the config somewhere else, e.g. for a backup. Autosaves still go to `config_path`.
+    fn save_as(&self, path: &Path) -> Result<(), Box<dyn Error>> {
+        fs::write(path, ron::to_string(&self)?)?;
         Ok(())
     }
 }
 
-#[derive(Clone, Copy)]
+#[derive(Clone)]
 enum NextPreview {
     Pixel(Option<Rgb8>),
-    Tri([Option<Rgb8>; 3])
+    // One link per seed row, while those are being woven side by side.
+    Multi(Vec<Option<Rgb8>>),
 }
-struct App<'a> {
+struct App {
     lines: Vec<Vec<Rgb8>>,
     rows: Vec<Vec<Rgb8>>,
+    // The link(s) woven before the current one, to double check what was just placed.
+    prev_pixel: NextPreview,
     current_pixel: NextPreview,
     next_pixel: NextPreview,
     ensure_current_on_screen: bool,
-    progress: &'a mut Progress,
+    // A working copy of the saved progress. run_app writes it back to the config when saving.
+    progress: Progress,
+    recent_ticks: VecDeque<Instant>,
+    pause_on_row_complete: bool,
+    // Set when a tick finishes a row, until the user acknowledges it.
+    just_completed_row: bool,
+    // Auto-advancing one link per play interval.
+    playing: bool,
+    session_start: Instant,
+    // Links ticked since the user last pressed `b`. Only kept for this run.
+    links_since_break: usize,
+    seed_rows: usize,
+    // Rows are reversed, so the stagger is drawn on the other rows.
+    mirrored: bool,
+    // Check off each row as its last link is woven.
+    auto_mark_rows: bool,
 }
-impl<'a> App<'a> {
-    fn initialize_lines(rows: &Vec<Vec<Rgb8>>, progress: &Progress) -> Vec<Vec<Rgb8>> {
-        if progress.row < 3 {
-            vec![
-                rows[0].iter().take(progress.col + 1).cloned().collect(),
-                rows[1].iter().take(progress.col).cloned().collect(),
-                rows[2].iter().take(progress.col + 1).cloned().collect(),
-            ]
+impl App {
+    // Even seed rows are one link ahead of odd ones, since each odd link joins the two before it.
+    fn seed_lead(row_idx: usize) -> usize {
+        if row_idx % 2 == 1 {
+            0
+        } else {
+            1
+        }
+    }
+
+    // The link at `col` of each seed row, shifted by that row's lead.
+    fn seed_links(rows: &[Vec<Rgb8>], seed_rows: usize, col: usize) -> Vec<Option<Rgb8>> {
+        (0..seed_rows)
+            .map(|idx| rows[idx].get(col + App::seed_lead(idx)).copied())
+            .collect()
+    }
 
+    fn initialize_lines(rows: &[Vec<Rgb8>], progress: &Progress, seed_rows: usize) -> Vec<Vec<Rgb8>> {
+        if progress.row < seed_rows {
+            (0..seed_rows)
+                .map(|idx| rows[idx].iter().take(progress.col + App::seed_lead(idx)).cloned().collect())
+                .collect()
         } else {
             let mut lines: Vec<Vec<Rgb8>> = rows.iter().take(progress.row).cloned().collect();
             lines.push(
-                rows[progress.row - 1]
+                rows[progress.row]
                     .iter()
                     .take(progress.col + 1)
                     .cloned()
@@ -177,88 +905,196 @@ impl<'a> App<'a> {
         }
     }
 
-    fn new(rows: Vec<Vec<Rgb8>>, progress: &'a mut Progress) -> App<'a> {
-        use NextPreview::*;
-        let lines = App::initialize_lines(&rows, progress);
-        let next_pixel = if progress.row >= 3 {
-            Pixel(rows[progress.row].get(progress.col).copied())
-        } else {
-            Tri([
-                rows[0].get(progress.col + 1).copied(),
-                rows[1].get(progress.col).copied(),
-                rows[2].get(progress.col + 1).copied(),
-            ])
-        };
-        let current_pixel = if progress.row >= 3 {
-            Pixel(rows[progress.row].get(progress.col - 1).copied())
-        } else {
-            Tri([
-                rows[0].get(progress.col).copied(),
-                rows[1].get(progress.col - 1).copied(),
-                rows[2].get(progress.col).copied(),
-            ])
-        };
-        App {
+    fn new(
+        rows: Vec<Vec<Rgb8>>,
+        mut progress: Progress,
+        pause_on_row_complete: bool,
+        seed_rows: usize,
+        mirrored: bool,
+        auto_mark_rows: bool,
+    ) -> App {
+        let seed_rows = seed_rows.clamp(1, rows.len().max(1));
+        // Progress saved with more seed rows can sit on an earlier row than the seed phase uses.
+        if progress.row < seed_rows - 1 {
+            progress.row = seed_rows - 1;
+        }
+        let mut app = App {
             ensure_current_on_screen: false,
-            lines,
+            lines: vec![],
             rows,
-            current_pixel,
-            next_pixel,
+            prev_pixel: NextPreview::Pixel(None),
+            current_pixel: NextPreview::Pixel(None),
+            next_pixel: NextPreview::Pixel(None),
             progress,
-        }
+            recent_ticks: VecDeque::with_capacity(RATE_WINDOW),
+            pause_on_row_complete,
+            just_completed_row: false,
+            playing: false,
+            session_start: Instant::now(),
+            links_since_break: 0,
+            seed_rows,
+            mirrored,
+            auto_mark_rows,
+        };
+        app.sync_to_progress();
+        app
+    }
 
+    // Recomputes the revealed lines and the link previews from `progress`.
+    fn sync_to_progress(&mut self) {
+        use NextPreview::*;
+        let (rows, progress, seed_rows) = (&self.rows, &self.progress, self.seed_rows);
+        self.lines = App::initialize_lines(rows, progress, seed_rows);
+        if progress.row >= seed_rows {
+            let link_before = |back: usize| {
+                progress
+                    .col
+                    .checked_sub(back)
+                    .and_then(|col| rows[progress.row].get(col))
+                    .copied()
+            };
+            self.next_pixel = Pixel(rows[progress.row].get(progress.col).copied());
+            self.current_pixel = Pixel(link_before(1));
+            self.prev_pixel = Pixel(link_before(2));
+        } else {
+            self.next_pixel = Multi(App::seed_links(rows, seed_rows, progress.col));
+            self.current_pixel = Multi(App::seed_links(rows, seed_rows, progress.col.saturating_sub(1)));
+            self.prev_pixel = match progress.col.checked_sub(2) {
+                Some(col) => Multi(App::seed_links(rows, seed_rows, col)),
+                None => Pixel(None),
+            };
+        }
     }
 }
 
 // Lifecycle methods
-impl<'a> App<'a> {
+impl App {
     fn tick(&mut self) {
+        self.record_tick(Instant::now());
+        self.links_since_break += 1;
         self.ensure_current_on_screen = true;
         self.progress.col += 1;
-        self.current_pixel = self.next_pixel;
+        self.prev_pixel = std::mem::replace(&mut self.current_pixel, self.next_pixel.clone());
         if self.is_done_with_line() {
+            if self.auto_mark_rows {
+                // The seed rows all finish together.
+                let first = if self.progress.row < self.seed_rows { 0 } else { self.progress.row };
+                for row in first..=self.progress.row {
+                    self.progress.set_row_done(row, true);
+                }
+            }
+            self.just_completed_row = self.pause_on_row_complete;
             self.progress.row += 1;
             self.progress.col = 0;
             self.lines.push(vec![]);
-            self.current_pixel = NextPreview::Pixel(self.rows.get(self.progress.row).and_then(|row| row.get(0).copied()));
+            self.current_pixel = NextPreview::Pixel(self.rows.get(self.progress.row).and_then(|row| row.first().copied()));
         }
-        if self.progress.row < 3 {
-            self.rows[0].get(self.lines[0].len()).map(|val| self.lines[0].push(*val));
-            self.rows[1].get(self.lines[1].len()).map(|val| self.lines[1].push(*val));
-            self.rows[2].get(self.lines[2].len()).map(|val| self.lines[2].push(*val));
-        } else {
-            if let Some(line) = self.lines.last_mut() {
-                self.rows[self.progress.row]
-                     .get(line.len())
-                     .map(|val| line.push(*val));
+        if self.progress.row < self.seed_rows {
+            for idx in 0..self.seed_rows {
+                if let Some(val) = self.rows[idx].get(self.lines[idx].len()) {
+                    self.lines[idx].push(*val);
+                }
+            }
+        } else if let Some(line) = self.lines.last_mut() {
+            if let Some(val) = self.rows[self.progress.row].get(line.len()) {
+                line.push(*val);
             }
         }
 
-        self.next_pixel = if self.progress.row >= 3 {
+        self.next_pixel = if self.progress.row >= self.seed_rows {
             NextPreview::Pixel(self.rows[self.progress.row].get(self.progress.col).copied())
         } else {
-            NextPreview::Tri([
-                self.rows[0].get(self.progress.col + 1).copied(),
-                self.rows[1].get(self.progress.col).copied(),
-                self.rows[2].get(self.progress.col + 1).copied(),
-            ])
+            NextPreview::Multi(App::seed_links(&self.rows, self.seed_rows, self.progress.col))
         };
     }
 
+    // Ticks up to `links` times, stopping early at the end of the pattern or of a row.
+    fn advance(&mut self, links: usize) {
+        for _ in 0..links {
+            if self.is_done() {
+                break;
+            }
+            self.tick();
+            if self.just_completed_row {
+                break;
+            }
+        }
+    }
+
     fn reset(&mut self) {
-        self.progress.reset();
-        self.lines = App::initialize_lines(&self.rows, &self.progress);
+        self.progress.reset(self.seed_rows);
+        self.sync_to_progress();
+        self.recent_ticks.clear();
+        self.just_completed_row = false;
+
+    }
+
+    // Moves the weave position to `target`'s row and col. Checked-off links are left alone.
+    fn jump_to(&mut self, target: &Progress) {
+        self.progress.row = target.row;
+        self.progress.col = target.col;
+        self.progress.clamp_to(&self.rows);
+        if self.progress.row < self.seed_rows - 1 {
+            self.progress.row = self.seed_rows - 1;
+        }
+        self.just_completed_row = false;
+        self.sync_to_progress();
+        self.ensure_current_on_screen = true;
+    }
+
+    // Flips every row end to end. The position stays the same number of links into the row, now
+    // counted from the other side, while checked-off links keep pointing at the same links.
+    fn toggle_mirror(&mut self) {
+        mirror_rows(&mut self.rows);
+        let rows = &self.rows;
+        self.progress.completed = self
+            .progress
+            .completed
+            .iter()
+            .map(|(row, col)| (*row, rows[*row].len() - 1 - col))
+            .collect();
+        self.mirrored = !self.mirrored;
+        self.sync_to_progress();
+        self.ensure_current_on_screen = true;
+    }
+
+    // The position of the most recently revealed link.
+    fn current_link(&self) -> Option<(usize, usize)> {
+        let row = self.lines.len().checked_sub(1)?;
+        let col = self.lines[row].len().checked_sub(1)?;
+        Some((row, col))
+    }
+
+    fn current_color(&self) -> Option<Rgb8> {
+        let (row, col) = self.current_link()?;
+        Some(self.lines[row][col])
+    }
+
+    fn toggle_row_done(&mut self) {
+        let row = self.progress.row;
+        self.progress.set_row_done(row, !self.progress.is_row_done(row));
+    }
 
+    fn toggle_completed(&mut self) {
+        if let Some(link) = self.current_link() {
+            if !self.progress.completed.remove(&link) {
+                self.progress.completed.insert(link);
+            }
+        }
     }
 
     fn is_done(&self) -> bool {
-        self.progress.row >= (self.rows.len() - 1)
-            && self.progress.col >= self.rows.last().map(|r| r.len()).unwrap_or(1) - 1
+        // Trailing empty rows have no links to weave, so finishing the last non-empty row is done.
+        let Some(last_row) = self.rows.iter().rposition(|r| !r.is_empty()) else {
+            return true;
+        };
+        self.progress.row > last_row
+            || (self.progress.row == last_row && self.progress.col >= self.rows[last_row].len() - 1)
     }
 
     fn is_done_with_line(&self) -> bool {
-        if self.progress.row < 3 {
-            let max_len = self.rows[0].len().max(self.rows[1].len()).max(self.rows[2].len());
+        if self.progress.row < self.seed_rows {
+            let max_len = self.rows[..self.seed_rows].iter().map(|r| r.len()).max().unwrap_or(0);
             self.progress.col >= max_len
         } else {
             self.progress.col >= self.rows[self.progress.row].len()
@@ -266,146 +1102,1873 @@ impl<'a> App<'a> {
     }
 }
 
-struct UIState {
-    vertical_scroll: ScrollbarState,
-    vertical_scroll_amount: usize,
-    horizontal_scroll: ScrollbarState,
-    horizontal_scroll_amount: usize,
-}
-impl UIState {
-    fn new(app: &App) -> UIState {
-        UIState {
-            horizontal_scroll: ScrollbarState::new(app.rows.iter().map(|r| r.len()).max().unwrap()),
-            horizontal_scroll_amount: (app.lines.last().unwrap().len() * 2).max(2) - 2,
-            vertical_scroll: ScrollbarState::default(),
-            vertical_scroll_amount: app.lines.len() - 3,
+// Statistics
+impl App {
+    fn record_tick(&mut self, now: Instant) {
+        let after_break = self
+            .recent_ticks
+            .back()
+            .is_some_and(|last| now.duration_since(*last) > RATE_RESET_PAUSE);
+        if after_break {
+            self.recent_ticks.clear();
+        }
+        if self.recent_ticks.len() == RATE_WINDOW {
+            self.recent_ticks.pop_front();
         }
+        self.recent_ticks.push_back(now);
     }
-}
 
-fn main() -> Result<(), Box<dyn Error>> {
-    let mut args = std::env::args();
-    args.next();
-    let file = match args.next() {
-        Some(f) => f,
-        None => return Err("File argument required.".into()),
-    };
-    println!("Opening file {}", file);
+    // Links from `col` to the end of its run of one color in the current row, once past the
+    // seed rows.
+    fn run_from(&self, col: usize) -> Option<usize> {
+        if self.progress.row < self.seed_rows {
+            return None;
+        }
+        let mut run_start = 0;
+        for (_, len) in rle_row(self.rows.get(self.progress.row)?) {
+            if col < run_start + len {
+                return Some(run_start + len - col);
+            }
+            run_start += len;
+        }
+        None
+    }
 
-    let project_dir = match ProjectDirs::from("page", "adno", "igp_pattern_printer") {
-        Some(proj_dirs) => proj_dirs.config_dir().to_owned(),
-        None => return Err("Could not find config directory".into()),
-    };
-    let mut config = Config::load(project_dir, Path::new(&file))?;
+    fn total_links(&self) -> usize {
+        self.rows.iter().map(|r| r.len()).sum()
+    }
 
-    let img = ImageReader::open(file)?.decode()?.to_rgb8();
+    // How many links of each color are still to be woven.
+    fn remaining_by_color(&self) -> HashMap<Rgb8, usize> {
+        let mut remaining: HashMap<Rgb8, usize> = HashMap::new();
+        for (row_idx, row) in self.rows.iter().enumerate() {
+            let woven = self.woven_in_row(row_idx);
+            for (col, color) in row.iter().enumerate() {
+                let count = remaining.entry(*color).or_insert(0);
+                if col >= woven {
+                    *count += 1;
+                }
+            }
+        }
+        remaining
+    }
 
-    let rows = build_rows(img, &mut config.color_map)?;
-    config.save()?;
+    // How many links at the start of `row` are woven, going by the position rather than by what
+    // the pattern view reveals: everything before the current link. The leading link of an even
+    // seed row is woven along with the row's first current link.
+    fn woven_in_row(&self, row: usize) -> usize {
+        let (current_row, col) = (self.progress.row, self.progress.col);
+        let woven = if current_row < self.seed_rows {
+            if row < self.seed_rows && col > 1 {
+                col - 1 + App::seed_lead(row)
+            } else {
+                0
+            }
+        } else if row < current_row {
+            usize::MAX
+        } else if row == current_row {
+            col.saturating_sub(1)
+        } else {
+            0
+        };
+        woven.min(self.rows.get(row).map_or(0, Vec::len))
+    }
 
-    //print_grid(rows, &mut config.color_map);
-    let mut term = setup_tui()?;
-    init_panic_hook();
-    run_app(&mut term, &mut config, rows)?;
-    config.save()?;
-    term.show_cursor()?;
-    teardown_tui()?;
-    Ok(())
-}
+    fn links_done(&self) -> usize {
+        self.lines.iter().map(|l| l.len()).sum()
+    }
 
-fn build_rows(
-    mut img: RgbImage,
-    color_map: &mut ColorMap,
-) -> Result<Vec<Vec<Rgb8>>, Box<dyn Error>> {
-    let mut rows: Vec<Vec<Rgb8>> = vec![];
-    let mut current_row: Vec<Rgb8> = vec![];
-    for y in 0..(img.height()) {
-        for x in 0..(img.width()) {
-            if img[(x, y)].to_rgb8() == SEPARATOR_COLOR {
-                continue;
-            }
-            current_row.push(img[(x, y)].to_rgb8());
-            color_map.ensure_mapped(img[(x, y)].to_rgb8())?;
-            flood_fill(&mut img, (x, y));
+    // Passed by the weave so far, or checked off by hand.
+    fn is_woven(&self, row: usize, col: usize) -> bool {
+        col < self.woven_in_row(row) || self.progress.completed.contains(&(row, col))
+    }
+
+    // The row being woven, or each seed row during the seed phase.
+    fn is_row_in_progress(&self, row: usize) -> bool {
+        if self.progress.row < self.seed_rows {
+            row < self.seed_rows
+        } else {
+            row == self.progress.row
+        }
+    }
+
+    // Links per minute over the recent tick window. None until there is enough data, or
+    // after a long break.
+    fn links_per_minute(&self) -> Option<f64> {
+        let first = self.recent_ticks.front()?;
+        let last = self.recent_ticks.back()?;
+        if last.elapsed() > RATE_RESET_PAUSE {
+            return None;
         }
-        if !current_row.is_empty() {
-            rows.push(current_row);
-            current_row = vec![];
+        let elapsed = last.duration_since(*first).as_secs_f64();
+        if self.recent_ticks.len() < 2 || elapsed <= 0. {
+            return None;
         }
+        Some((self.recent_ticks.len() - 1) as f64 * 60. / elapsed)
     }
-    Ok(rows)
-}
 
-fn setup_tui() -> Result<Terminal<impl Backend + io::Write>, Box<dyn Error>> {
-    enable_raw_mode()?;
-    let stdout = io::stdout();
-    let mut backend = CrosstermBackend::new(stdout);
-    execute!(backend, EnterAlternateScreen, EnableMouseCapture)?;
-    backend.hide_cursor()?;
-    Ok(Terminal::new(backend)?)
+    fn eta(&self) -> Option<Duration> {
+        let rate = self.links_per_minute()?;
+        let remaining = self.total_links().saturating_sub(self.links_done());
+        Some(Duration::from_secs_f64(remaining as f64 * 60. / rate))
+    }
+
+    // The current link as 1-based (row, link), as `--diff` and the other printed positions count.
+    // The current link is the one just before `col`, so its 1-based index is `col` itself, except
+    // right after a row is finished: `col` is back to 0 while the new row's first link is current.
+    fn display_position(&self) -> (usize, usize) {
+        (self.progress.row + 1, self.progress.col.max(1))
+    }
+
+    // A small JSON summary for stream overlays and other tools that poll the file. Written to a
+    // temporary file first so readers never see half of it.
+    fn write_progress_json(&self, path: &Path) -> io::Result<()> {
+        let row_len = self.rows.get(self.progress.row).map(|r| r.len()).unwrap_or(0);
+        let (row, col) = self.display_position();
+        let rate = match self.links_per_minute() {
+            Some(rate) => format!("{:.1}", rate),
+            None => "null".to_owned(),
+        };
+        let json = format!(
+            "{{\"row\":{},\"rows\":{},\"col\":{},\"row_length\":{},\"links_done\":{},\"total_links\":{},\"percent\":{:.1},\"links_per_minute\":{}}}\n",
+            row,
+            self.rows.len(),
+            col,
+            row_len,
+            self.links_done(),
+            self.total_links(),
+            self.links_done() as f64 * 100. / self.total_links().max(1) as f64,
+            rate,
+        );
+        let tmp_path = path.with_extension("json.tmp");
+        fs::write(&tmp_path, json)?;
+        fs::rename(tmp_path, path)
+    }
 }
 
-fn teardown_tui() -> Result<(), Box<dyn Error>> {
-    disable_raw_mode()?;
-    execute!(
-        io::stdout(),
-        LeaveAlternateScreen,
-        DisableMouseCapture
-    )?;
+// What the text typed into the instruction line is for.
+enum InputPurpose {
+    ColorName(Rgb8),
+    ColorChar(Rgb8, String),
+    Note((usize, usize)),
+    JumpToRow,
+}
 
-    Ok(())
+struct TextInput {
+    purpose: InputPurpose,
+    buffer: String,
 }
-fn init_panic_hook() {
-    use std::panic::{set_hook, take_hook};
-    let original_hook = take_hook();
-    set_hook(Box::new(move |panic_info| {
-        let _ = teardown_tui();
+
+impl TextInput {
+    fn prompt(&self) -> &'static str {
+        match self.purpose {
+            InputPurpose::ColorName(_) => "New color name",
+            InputPurpose::ColorChar(..) => "New 1 character description",
+            InputPurpose::Note(_) => "Note for this link (empty to delete)",
+            InputPurpose::JumpToRow => "Jump to row, or row,link",
+        }
+    }
+
+    // The color being named, if the input is about one.
+    fn color(&self) -> Option<Rgb8> {
+        match self.purpose {
+            InputPurpose::ColorName(color) | InputPurpose::ColorChar(color, _) => Some(color),
+            InputPurpose::Note(_) | InputPurpose::JumpToRow => None,
+        }
+    }
+
+    // Goes back from the description to the name typed before it, so it can be fixed.
+    fn back(self) -> TextInput {
+        match self.purpose {
+            InputPurpose::ColorChar(color, full_name) => TextInput {
+                purpose: InputPurpose::ColorName(color),
+                buffer: full_name,
+            },
+            purpose => TextInput { purpose, buffer: self.buffer },
+        }
+    }
+}
+
+struct UIState {
+    input: Option<TextInput>,
+    vertical_scroll: ScrollbarState,
+    vertical_scroll_amount: usize,
+    horizontal_scroll: ScrollbarState,
+    horizontal_scroll_amount: usize,
+    stagger_parity: bool,
+    // Height and width inside the pattern pane's border, as of the last draw.
+    view_size: (usize, usize),
+    // When non-empty, links of any other color are dimmed.
+    focused_colors: HashSet<Rgb8>,
+    source_preview: Option<SourcePreview>,
+    split: Option<SplitView>,
+    // Index into ColorMap::sorted_entries of the highlighted color while the color list is open.
+    color_list: Option<usize>,
+    // Show how many links in a row share the previewed color.
+    show_runs: bool,
+    // Show each link as its full color name instead of its one-char descriptor.
+    full_names: bool,
+    // Show the current link's row and 1-based position within it, for written instructions.
+    show_indices: bool,
+    // Set after `m` or `'`, until the bookmark name is typed.
+    pending_bookmark: Option<BookmarkAction>,
+    // Set after `q` while confirm_quit is on, until the user answers.
+    confirming_quit: bool,
+    // The instruction line's list of keys, from the keymap.
+    controls_help: String,
+    // Typed digits waiting for the key they repeat.
+    count: Option<usize>,
+    // Unmapped colors the user declined to name, so they aren't asked about again this session.
+    dismissed_unmapped: HashSet<Rgb8>,
+    // Something that went wrong without ending the session, shown until the next key.
+    warning: Option<String>,
+}
+
+#[derive(Clone, Copy, Debug)]
+enum BookmarkAction {
+    Set,
+    Jump,
+}
+
+// Everything a key can be bound to in the TUI's keymap. Listed in the order the controls line
+// shows them.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+enum Action {
+    Quit,
+    Next,
+    SkipAhead,
+    AutoPlay,
+    ScrollLeft,
+    ScrollDown,
+    ScrollUp,
+    ScrollRight,
+    ScrollTop,
+    ScrollBottom,
+    ScrollRowStart,
+    ScrollRowEnd,
+    GoToCurrent,
+    FocusColor,
+    ClearFocus,
+    ColorList,
+    ToggleSourceImage,
+    ToggleOutlines,
+    ToggleSplit,
+    SwitchPane,
+    ToggleLinkDone,
+    ToggleRowDone,
+    TakeBreak,
+    ToggleRuns,
+    ToggleFullNames,
+    ToggleIndices,
+    SetBookmark,
+    JumpToBookmark,
+    JumpToRow,
+    ToggleStagger,
+    ToggleMirror,
+    EditColor,
+    EditNote,
+    Undo,
+    Redo,
+    Reset,
+}
+
+impl Action {
+    const ALL: [Action; 36] = {
+        use Action::*;
+        [
+            Quit, Next, SkipAhead, AutoPlay, ScrollLeft, ScrollDown, ScrollUp, ScrollRight,
+            ScrollTop, ScrollBottom, ScrollRowStart, ScrollRowEnd, GoToCurrent, FocusColor,
+            ClearFocus, ColorList, ToggleSourceImage, ToggleOutlines, ToggleSplit, SwitchPane,
+            ToggleLinkDone,
+            ToggleRowDone, TakeBreak, ToggleRuns, ToggleFullNames, ToggleIndices, SetBookmark,
+            JumpToBookmark, JumpToRow, ToggleStagger, ToggleMirror, EditColor, EditNote, Undo, Redo,
+            Reset,
+        ]
+    };
+
+    fn description(self) -> &'static str {
+        use Action::*;
+        match self {
+            Quit => "Quit",
+            Next => "[count] Next link(s)",
+            SkipAhead => "Skip 30 links",
+            AutoPlay => "Auto-play",
+            ScrollLeft => "Scroll left",
+            ScrollDown => "Scroll down",
+            ScrollUp => "Scroll up",
+            ScrollRight => "Scroll right",
+            ScrollTop => "Jump to top",
+            ScrollBottom => "Jump to bottom",
+            ScrollRowStart => "Jump to row start",
+            ScrollRowEnd => "Jump to row end",
+            GoToCurrent => "Go to current",
+            FocusColor => "Focus color",
+            ClearFocus => "Clear focus",
+            ColorList => "Color list",
+            ToggleSourceImage => "Source image",
+            ToggleOutlines => "Outline overlay",
+            ToggleSplit => "Split view",
+            SwitchPane => "Switch pane",
+            ToggleLinkDone => "Mark link done",
+            ToggleRowDone => "Mark row done",
+            TakeBreak => "Take a break",
+            ToggleRuns => "Run lengths",
+            ToggleFullNames => "Full names",
+            ToggleIndices => "Link indices",
+            SetBookmark => "Set bookmark",
+            JumpToBookmark => "Jump to bookmark",
+            JumpToRow => "Jump to row",
+            ToggleStagger => "Flip stagger",
+            ToggleMirror => "Mirror",
+            EditColor => "Edit color",
+            EditNote => "Note",
+            Undo => "Undo edit",
+            Redo => "Redo edit",
+            Reset => "Reset progress",
+        }
+    }
+}
+
+// The name a key goes by in the keymap: the character itself, or e.g. `Space`, `Left`, `F1`.
+fn key_name(code: KeyCode) -> Option<String> {
+    Some(match code {
+        KeyCode::Char(' ') => "Space".to_owned(),
+        KeyCode::Char(c) => c.to_string(),
+        KeyCode::F(n) => format!("F{}", n),
+        KeyCode::Enter => "Enter".to_owned(),
+        KeyCode::Tab => "Tab".to_owned(),
+        KeyCode::Backspace => "Backspace".to_owned(),
+        KeyCode::Delete => "Delete".to_owned(),
+        KeyCode::Esc => "Esc".to_owned(),
+        KeyCode::Left => "Left".to_owned(),
+        KeyCode::Right => "Right".to_owned(),
+        KeyCode::Up => "Up".to_owned(),
+        KeyCode::Down => "Down".to_owned(),
+        KeyCode::Home => "Home".to_owned(),
+        KeyCode::End => "End".to_owned(),
+        KeyCode::PageUp => "PageUp".to_owned(),
+        KeyCode::PageDown => "PageDown".to_owned(),
+        _ => return None,
+    })
+}
+
+// "q: Quit | Space: [count] Next link(s) | ..." for every bound action.
+fn controls_help(keymap: &HashMap<String, Action>) -> String {
+    Action::ALL
+        .iter()
+        .filter_map(|action| {
+            let keys = bound_keys(keymap, *action);
+            if keys.is_empty() {
+                return None;
+            }
+            Some(format!("{}: {}", keys, action.description()))
+        })
+        .collect::<Vec<_>>()
+        .join(" | ")
+}
+
+// Every key bound to `action`, joined with `/`, or an empty string if it is unbound.
+fn bound_keys(keymap: &HashMap<String, Action>, action: Action) -> String {
+    let mut keys: Vec<&str> = keymap
+        .iter()
+        .filter(|(_, bound)| **bound == action)
+        .map(|(key, _)| key.as_str())
+        .collect();
+    // Single characters first, so `h/Left` reads like the old help.
+    keys.sort_by_key(|key| (key.chars().count(), *key));
+    keys.join("/")
+}
+
+// The pattern pane shown twice side by side. Only the active pane follows the scroll keys; the
+// other one keeps its offsets here until Tab swaps them back in.
+#[derive(Clone, Copy, Debug)]
+struct SplitView {
+    active_right: bool,
+    // (vertical, horizontal), like Config::scroll.
+    other_scroll: (usize, usize),
+}
+
+// The decoded source image, shown downsampled in place of the pattern as a sanity check that the
+// right file was loaded.
+struct SourcePreview {
+    image: RgbImage,
+    // The image with outline pixels in OUTLINE_OVERLAY_COLOR, to check what the parse took as
+    // outline.
+    outlines: RgbImage,
+    visible: bool,
+    show_outlines: bool,
+    // Downsampled lines for the last pane size and image, so the image isn't resized on every draw.
+    cached: Option<((u16, u16, bool), Vec<Line<'static>>)>,
+}
+
+impl SourcePreview {
+    fn new(image: RgbImage, outlines: RgbImage, visible: bool) -> SourcePreview {
+        SourcePreview { image, outlines, visible, show_outlines: false, cached: None }
+    }
+
+    fn title(&self) -> &'static str {
+        if self.show_outlines {
+            "Source image, outlines in magenta"
+        } else {
+            "Source image"
+        }
+    }
+
+    // Shows the outline overlay, or hides the preview if the overlay was already up.
+    fn toggle_outlines(&mut self) {
+        if self.visible && self.show_outlines {
+            self.visible = false;
+        } else {
+            self.visible = true;
+            self.show_outlines = true;
+        }
+    }
+
+    // Fits the image into `width` x `height` cells, two pixels per cell using a half block with
+    // the upper pixel as foreground and the lower one as background.
+    fn lines(&mut self, width: u16, height: u16) -> &[Line<'static>] {
+        use image::imageops::{resize, FilterType};
+
+        let key = (width, height, self.show_outlines);
+        if self.cached.as_ref().map(|(cached_key, _)| *cached_key) != Some(key) {
+            // Sampling keeps the overlay's outline pixels exact instead of blending them away.
+            let (image, filter) = if self.show_outlines {
+                (&self.outlines, FilterType::Nearest)
+            } else {
+                (&self.image, FilterType::Triangle)
+            };
+            let (img_width, img_height) = image.dimensions();
+            let scale = f64::min(
+                width as f64 / img_width as f64,
+                (height as f64 * 2.) / img_height as f64,
+            );
+            let thumb_width = ((img_width as f64 * scale) as u32).max(1);
+            let thumb_height = ((img_height as f64 * scale) as u32).max(1);
+            let thumb = resize(image, thumb_width, thumb_height, filter);
+            let lines = (0..thumb_height)
+                .step_by(2)
+                .map(|y| {
+                    Line::from(
+                        (0..thumb_width)
+                            .map(|x| {
+                                let top = thumb[(x, y)].0;
+                                let mut style = Style::new().fg(Color::Rgb(top[0], top[1], top[2]));
+                                if y + 1 < thumb_height {
+                                    let bottom = thumb[(x, y + 1)].0;
+                                    style = style.bg(Color::Rgb(bottom[0], bottom[1], bottom[2]));
+                                }
+                                Span::styled("\u{2580}", style)
+                            })
+                            .collect::<Vec<_>>(),
+                    )
+                })
+                .collect();
+            self.cached = Some((key, lines));
+        }
+        &self.cached.as_ref().unwrap().1
+    }
+}
+impl UIState {
+    fn new(app: &App, stagger_parity: bool, scroll: Option<(usize, usize)>) -> UIState {
+        let mut ui_state = UIState {
+            input: None,
+            stagger_parity,
+            view_size: (0, 0),
+            focused_colors: HashSet::new(),
+            source_preview: None,
+            split: None,
+            dismissed_unmapped: HashSet::new(),
+            count: None,
+            pending_bookmark: None,
+            confirming_quit: false,
+            warning: None,
+            controls_help: String::new(),
+            show_runs: false,
+            full_names: false,
+            show_indices: false,
+            color_list: None,
+            horizontal_scroll: ScrollbarState::new(app.rows.iter().map(|r| r.len()).max().unwrap()),
+            horizontal_scroll_amount: (app.lines.last().unwrap().len() * 2).max(2) - 2,
+            vertical_scroll: ScrollbarState::default(),
+            vertical_scroll_amount: app.lines.len().saturating_sub(app.seed_rows),
+        };
+        if let Some((vertical, horizontal)) = scroll {
+            ui_state.vertical_scroll_amount = vertical.min(UIState::max_vertical_scroll(app));
+            ui_state.horizontal_scroll_amount = horizontal.min(ui_state.max_horizontal_scroll(app));
+        }
+        ui_state
+    }
+
+    // The furthest the view may scroll while still showing some content. This is at least as far
+    // as ensure_scroll_to_visible's overscroll will ever put it.
+    fn max_vertical_scroll(app: &App) -> usize {
+        app.lines.len().saturating_sub(1)
+    }
+
+    fn max_horizontal_scroll(&self, app: &App) -> usize {
+        (ROW_GUTTER_WIDTH + app.rows.iter().map(|r| r.len()).max().unwrap_or(0) * self.cell_period())
+            .saturating_sub(1)
+    }
+
+    // Columns taken by one link and the space after it.
+    fn cell_period(&self) -> usize {
+        if self.full_names {
+            NAME_CELL_WIDTH + 1
+        } else {
+            2
+        }
+    }
+
+    // Horizontal scroll in one-char columns, which is how it's saved to the config.
+    fn saved_horizontal_scroll(&self) -> usize {
+        self.horizontal_scroll_amount / self.cell_period() * 2
+    }
+
+    // Keeps the same links in view when the cell width changes.
+    fn toggle_full_names(&mut self) {
+        let old_period = self.cell_period();
+        self.full_names = !self.full_names;
+        let new_period = self.cell_period();
+        let rescale = |scroll: usize| scroll / old_period * new_period;
+        self.horizontal_scroll_amount = rescale(self.horizontal_scroll_amount);
+        if let Some(split) = &mut self.split {
+            split.other_scroll.1 = rescale(split.other_scroll.1);
+        }
+    }
+
+    fn toggle_focus(&mut self, color: Rgb8) {
+        if !self.focused_colors.remove(&color) {
+            self.focused_colors.insert(color);
+        }
+    }
+
+    // Opening the split starts both panes at the current position.
+    fn toggle_split(&mut self) {
+        self.split = match self.split {
+            Some(_) => None,
+            None => Some(SplitView {
+                active_right: false,
+                other_scroll: (self.vertical_scroll_amount, self.horizontal_scroll_amount),
+            }),
+        };
+    }
+
+    fn switch_pane(&mut self) {
+        if let Some(split) = self.split.as_mut() {
+            split.active_right = !split.active_right;
+            let active_scroll = (self.vertical_scroll_amount, self.horizontal_scroll_amount);
+            (self.vertical_scroll_amount, self.horizontal_scroll_amount) = split.other_scroll;
+            split.other_scroll = active_scroll;
+        }
+    }
+
+    fn scroll_to_bottom(&mut self, app: &App) {
+        self.vertical_scroll_amount = app.lines.len().saturating_sub(self.view_size.0);
+    }
+
+    fn scroll_to_row_end(&mut self, app: &App) {
+        let widest = ROW_GUTTER_WIDTH + app.lines.iter().map(|l| l.len()).max().unwrap_or(0) * self.cell_period();
+        self.horizontal_scroll_amount = widest.saturating_sub(self.view_size.1);
+    }
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let cli = Cli::parse();
+    if let Some(config_path) = &cli.config {
+        return color_map_only(config_path, cli.export_palette.as_deref());
+    }
+    let file = cli.file.expect("clap requires a file without --config");
+    if !cli.status {
+        eprintln!("Opening file {}", file.display());
+    }
+
+    let project_dir = match ProjectDirs::from("page", "adno", "igp_pattern_printer") {
+        Some(proj_dirs) => proj_dirs.config_dir().to_owned(),
+        None => return Err("Could not find config directory".into()),
+    };
+    let mut config = Config::load(project_dir, &file)?;
+
+    let img = load_image(&file)?;
+    let palette = read_palette(&file)
+        .map_err(|e| format!("Could not read the palette of {}: {}", file.display(), e))?;
+    if !cli.separators.is_empty() {
+        config.separators = cli.separators;
+    }
+    if !cli.background_colors.is_empty() {
+        config.background_colors = cli.background_colors.into_iter().collect();
+    }
+    if let Some(seed_rows) = cli.seed_rows {
+        config.set_seed_rows(seed_rows.get());
+    }
+    if cli.mirror {
+        config.mirror = true;
+    }
+    config.color_map.suggest_distance = cli.suggest_distance;
+    let mut builder = RowBuilder::new(
+        img,
+        config.separators.clone(),
+        config.background_colors.clone(),
+        cli.connectivity,
+        cli.pixel_mode,
+    );
+    builder.max_region = cli.max_region;
+    if let Some((x, y)) = cli.separator_at {
+        let separator = builder.set_separator_from(x, y)?;
+        eprintln!("Using outline color {} from ({}, {})", separator.to_hex(), x, y);
+        config.separators = vec![separator];
+    }
+    if let Some(max_distance) = cli.merge_colors {
+        let RowBuilder { img, is_separator, .. } = &mut builder;
+        let merged = quantize_colors(img, max_distance, is_separator);
+        eprintln!("Merged {} similar colors", merged);
+    }
+    if cli.autocrop {
+        let (x, y, width, height) = content_bounds(&builder.img, |color| builder.is_separator(color))
+            .ok_or("No non-separator pixels found; check the separator color")?;
+        if (width, height) != builder.img.dimensions() {
+            eprintln!("Cropped to {}x{} at ({}, {})", width, height, x, y);
+            builder.img = image::imageops::crop_imm(&builder.img, x, y, width, height).to_image();
+        }
+    }
+
+    let estimated_links = builder.estimate_links();
+    if estimated_links > cli.max_links {
+        return Err(format!(
+            "This image looks like it has up to {} links, more than the limit of {}. If it is a photo, \
+             scale it down so each link is a single blob of color; otherwise check the outline color \
+             (--separator or --separator-at). Use --max-links to raise the limit.",
+            estimated_links, cli.max_links
+        )
+        .into());
+    }
+
+    // A palette already is the exact color set, so name each entry instead of scanning. Merging
+    // colors rewrites the image, though, which would leave the palette stale.
+    let colors = match palette {
+        Some(palette) if cli.merge_colors.is_none() => {
+            let mut seen = HashSet::new();
+            palette
+                .into_iter()
+                .filter(|color| builder.is_link_color(*color) && seen.insert(*color))
+                .collect()
+        }
+        _ => builder.discover_colors(),
+    };
+    if cli.check {
+        let unmapped: Vec<Rgb8> = colors
+            .iter()
+            .copied()
+            .filter(|color| !config.color_map.is_mapped(*color))
+            .collect();
+        if !unmapped.is_empty() {
+            println!("Unmapped colors:");
+            for color in &unmapped {
+                println!("  {}", color.to_hex());
+            }
+            return Err(format!("{} colors have no name", unmapped.len()).into());
+        }
+        let rows = builder.build(&mut config.color_map)?;
+        let links: usize = rows.iter().map(|r| r.len()).sum();
+        println!("OK: {} rows, {} links, {} colors", rows.len(), links, colors.len());
+        let mut legend: Vec<_> = config
+            .color_map
+            .entries()
+            .filter(|(color, _, _)| colors.contains(color))
+            .collect();
+        legend.sort_by_key(|(_, full_name, _)| *full_name);
+        for (color, full_name, one_char) in legend {
+            println!("  {} {} {}", one_char, color.to_hex(), full_name);
+        }
+        return Ok(());
+    }
+    if cli.verify {
+        let report = verify(&colors, &config.color_map);
+        if !report.unused.is_empty() {
+            println!("Names for colors not in the image:");
+            for (color, full_name) in &report.unused {
+                println!("  {} {}", color.to_hex(), full_name);
+            }
+        }
+        if !report.unmapped.is_empty() {
+            println!("Unmapped colors:");
+            for color in &report.unmapped {
+                println!("  {}", color.to_hex());
+            }
+            return Err(format!("{} colors have no name", report.unmapped.len()).into());
+        }
+        println!("OK: all {} colors are named, {} unused names", colors.len(), report.unused.len());
+        return Ok(());
+    }
+    if cli.status {
+        if config.color_map.count_unmapped(&colors) > 0 {
+            return Err("Some colors have no name yet; open the pattern normally first".into());
+        }
+        let rows = builder.build(&mut config.color_map)?;
+        let rows = arrange_rows(rows, cli.reflow, &mut config);
+        println!("{}", status_line(rows, &config));
+        return Ok(());
+    }
+    // Everything from here on may save the config, which would clobber another instance's progress.
+    let _lock = ProgressLock::acquire(config.lock_path(), cli.force)?;
+    let unmapped = config.color_map.count_unmapped(&colors);
+    if unmapped > 0 {
+        eprintln!("{} of {} colors need a name", unmapped, colors.len());
+    }
+    // Saved after each name, so naming a big palette can be stopped and picked up later.
+    for color in colors {
+        if !config.color_map.is_mapped(color) {
+            config.color_map.ensure_mapped(color)?;
+            config.save()?;
+        }
+    }
+    if let Some(path) = cli.export_palette {
+        config.color_map.export_gpl(&path)?;
+        println!("Wrote {}", path.display());
+        return Ok(());
+    }
+    if cli.region_stats {
+        let mut sizes = vec![];
+        let rows = builder.build_with_region_sizes(&mut config.color_map, &mut sizes)?;
+        config.save()?;
+        print_region_stats(&rows, &sizes, &config.color_map);
+        return Ok(());
+    }
+    let rows = builder.build(&mut config.color_map)?;
+    let rows = arrange_rows(rows, cli.reflow, &mut config);
+    config.save()?;
+    if let Some(other_file) = cli.diff {
+        let other_img = load_image(&other_file)?;
+        let other_builder = RowBuilder::new(
+            other_img,
+            config.separators.clone(),
+            builder.background_colors.clone(),
+            builder.connectivity,
+            builder.pixel_mode,
+        );
+        for color in other_builder.discover_colors() {
+            config.color_map.ensure_mapped(color)?;
+        }
+        let other_rows = arranged_like(other_builder.build(&mut config.color_map)?, &config);
+        config.save()?;
+        print_diff(&rows, &other_rows, &config.color_map);
+        return Ok(());
+    }
+    if let Some(path) = cli.save_config {
+        config.save_as(&path)?;
+        println!("Wrote {}", path.display());
+        return Ok(());
+    }
+    let remaining;
+    let export_rows = if cli.remaining {
+        remaining = remaining_rows(&rows, &config.progress, config.seed_rows);
+        if remaining.is_empty() {
+            return Err("The pattern is finished; there is nothing left to export".into());
+        }
+        &remaining
+    } else {
+        &rows
+    };
+    // Pages and the remaining rows start on even rows, so the whole pattern's stagger holds for them.
+    let flipped = stagger_flipped(config.stagger_parity, config.mirror);
+    let pages = match (cli.page_rows, cli.page_cols) {
+        (None, None) => None,
+        (page_rows, page_cols) => Some(export::paginate(
+            export_rows,
+            page_rows.map_or(usize::MAX, NonZeroUsize::get),
+            page_cols.map_or(usize::MAX, NonZeroUsize::get),
+            cli.page_overlap,
+        )?),
+    };
+    if let Some(path) = cli.export_png {
+        match &pages {
+            Some(pages) => {
+                for page in pages {
+                    let page_path = page.path_for(&path);
+                    export::export_image(
+                        &page.rows,
+                        &config.color_map,
+                        &page_path,
+                        cli.cell_size,
+                        cli.grid_labels,
+                        flipped,
+                        Some(page),
+                    )?;
+                    println!("Wrote {}", page_path.display());
+                }
+            }
+            None => {
+                export::export_image(
+                    export_rows,
+                    &config.color_map,
+                    &path,
+                    cli.cell_size,
+                    cli.grid_labels,
+                    flipped,
+                    None,
+                )?;
+                println!("Wrote {}", path.display());
+            }
+        }
+        return Ok(());
+    }
+    if let Some(path) = cli.export_svg {
+        match &pages {
+            Some(pages) => {
+                for page in pages {
+                    let page_path = page.path_for(&path);
+                    let hex_size = cli.cell_size as f64;
+                    let svg = export::export_svg(&page.rows, &config.color_map, hex_size, flipped, Some(page));
+                    fs::write(&page_path, svg)?;
+                    println!("Wrote {}", page_path.display());
+                }
+            }
+            None => {
+                let svg = export::export_svg(export_rows, &config.color_map, cli.cell_size as f64, flipped, None);
+                fs::write(&path, svg)?;
+                println!("Wrote {}", path.display());
+            }
+        }
+        return Ok(());
+    }
+    if let Some(path) = cli.export_chart {
+        export::export_chart(export_rows, &config.color_map, &path, export::ChartFormat::from_path(&path))?;
+        println!("Wrote {}", path.display());
+        return Ok(());
+    }
+    // The alternate screen only makes sense on a terminal; when piped, print the pattern instead.
+    if !io::stdout().is_terminal() {
+        let woven = cli.annotate_progress.then(|| {
+            App::new(rows.clone(), config.progress.clone(), false, config.seed_rows, config.mirror, false)
+        });
+        let printed = match &pages {
+            Some(pages) => pages.iter().try_for_each(|page| {
+                use io::Write;
+                writeln!(io::stdout(), "{}", page.header())?;
+                let woven = woven.as_ref().map(|app| (app, (page.top, page.left)));
+                print_grid(&page.rows, &config.color_map, flipped, woven)
+            }),
+            None => print_grid(export_rows, &config.color_map, flipped, woven.as_ref().map(|app| (app, (0, 0)))),
+        };
+        return match printed {
+            // The reader (e.g. `head` or `less`) went away early; that's fine.
+            Err(e) if e.kind() == io::ErrorKind::BrokenPipe => Ok(()),
+            result => Ok(result?),
+        };
+    }
+    warn_on_row_length_anomalies(&rows)?;
+
+    let mut term = setup_tui()?;
+    let _guard = TuiGuard;
+    init_panic_hook();
+    let outlines = builder.outline_overlay();
+    let source_preview = SourcePreview::new(builder.img, outlines, cli.show_image);
+    let progress_json = cli.emit_progress_json.then(|| config.progress_json_path());
+    let result = run_app(
+        &mut term,
+        &mut config,
+        rows,
+        source_preview,
+        progress_json,
+        Duration::from_millis(cli.play_interval),
+    );
+    // run_app hands the session's progress back even when it fails, so save it before reporting.
+    config.save()?;
+    term.show_cursor()?;
+    result
+}
+
+// Splits a pattern image into rows of links. Each blob of non-separator pixels is one link.
+struct RowBuilder {
+    img: RgbImage,
+    is_separator: SeparatorPredicate,
+    // Skipped like separators, but a link's flood fill still spreads through them.
+    background_colors: HashSet<Rgb8>,
+    connectivity: Connectivity,
+    pixel_mode: PixelMode,
+    // Pixel count past which a flood fill is stopped and the build fails.
+    max_region: usize,
+}
+
+impl RowBuilder {
+    fn new(
+        img: RgbImage,
+        separators: Vec<Rgb8>,
+        background_colors: HashSet<Rgb8>,
+        connectivity: Connectivity,
+        pixel_mode: PixelMode,
+    ) -> RowBuilder {
+        let separators = if separators.is_empty() { default_separators() } else { separators };
+        RowBuilder {
+            background_colors,
+            connectivity,
+            pixel_mode,
+            ..RowBuilder::with_separator_predicate(img, Box::new(move |color| separators.contains(&color)))
+        }
+    }
+
+    // For outlines that aren't a fixed set of colors, e.g. any gray or anything darker than some
+    // threshold. Starts with no background colors, 4-connectivity, and blob mode.
+    fn with_separator_predicate(img: RgbImage, is_separator: SeparatorPredicate) -> RowBuilder {
+        RowBuilder {
+            img,
+            is_separator,
+            background_colors: HashSet::new(),
+            connectivity: Connectivity::Four,
+            pixel_mode: PixelMode::Blob,
+            max_region: DEFAULT_MAX_REGION,
+        }
+    }
+
+    fn is_separator(&self, color: Rgb8) -> bool {
+        (self.is_separator)(color)
+    }
+
+    // A copy of the image with every pixel the parse treats as outline painted in
+    // OUTLINE_OVERLAY_COLOR, so links eaten by a too-broad outline color stand out.
+    fn outline_overlay(&self) -> RgbImage {
+        let mut overlay = self.img.clone();
+        for pixel in overlay.pixels_mut() {
+            if self.is_separator(pixel.to_rgb8()) {
+                *pixel = Rgb(OUTLINE_OVERLAY_COLOR.0);
+            }
+        }
+        overlay
+    }
+
+    fn is_link_color(&self, color: Rgb8) -> bool {
+        !self.is_separator(color) && !self.background_colors.contains(&color)
+    }
+
+    // Uses the color of the pixel at (x, y) as the only outline color, returning it.
+    fn set_separator_from(&mut self, x: u32, y: u32) -> Result<Rgb8, Box<dyn Error>> {
+        let pixel = self.img.get_pixel_checked(x, y).ok_or_else(|| {
+            format!(
+                "({}, {}) is outside the {}x{} image",
+                x,
+                y,
+                self.img.width(),
+                self.img.height()
+            )
+        })?;
+        let separator = pixel.to_rgb8();
+        self.is_separator = Box::new(move |color| color == separator);
+        Ok(separator)
+    }
+
+    // A rough upper bound on the link count without flood filling: counts link pixels whose upper
+    // and left neighbours both differ from them, which happens a few times per blob but on almost
+    // every pixel of a photo. Exact in PerPixel mode, where every link pixel counts.
+    fn estimate_links(&self) -> usize {
+        let img = &self.img;
+        let per_pixel = self.pixel_mode == PixelMode::PerPixel;
+        (0..img.height())
+            .into_par_iter()
+            .map(|y| {
+                (0..img.width())
+                    .filter(|x| {
+                        let color = img[(*x, y)];
+                        self.is_link_color(color.to_rgb8())
+                            && (per_pixel
+                                || ((*x == 0 || img[(*x - 1, y)] != color) && (y == 0 || img[(*x, y - 1)] != color)))
+                    })
+                    .count()
+            })
+            .sum()
+    }
+
+    // Collects every distinct non-separator color in the image, in the order they are first
+    // encountered while scanning. Rows are scanned in parallel so that all color prompts can be
+    // asked up front instead of being interleaved with the (sequential) flood fill.
+    fn discover_colors(&self) -> Vec<Rgb8> {
+        let img = &self.img;
+        let per_row: Vec<Vec<Rgb8>> = (0..img.height())
+            .into_par_iter()
+            .map(|y| {
+                let mut seen = HashSet::new();
+                (0..img.width())
+                    .map(|x| img[(x, y)].to_rgb8())
+                    .filter(|color| self.is_link_color(*color) && seen.insert(*color))
+                    .collect()
+            })
+            .collect();
+
+        let mut seen = HashSet::new();
+        per_row
+            .into_iter()
+            .flatten()
+            .filter(|color| seen.insert(*color))
+            .collect()
+    }
+
+    // Leaves the image untouched, so the builder can be rebuilt with different settings.
+    fn build(&self, color_map: &mut ColorMap) -> Result<Vec<Vec<Rgb8>>, Box<dyn Error>> {
+        self.build_with_region_sizes(color_map, &mut vec![])
+    }
+
+    // Like `build`, but also records how many pixels each link covered into `region_sizes`.
+    fn build_with_region_sizes(
+        &self,
+        color_map: &mut ColorMap,
+        region_sizes: &mut RegionSizes,
+    ) -> Result<Vec<Vec<Rgb8>>, Box<dyn Error>> {
+        let img = &self.img;
+        let mut visited = vec![false; img.width() as usize * img.height() as usize];
+        let mut rows: Vec<Vec<Rgb8>> = vec![];
+        let mut current_row: Vec<Rgb8> = vec![];
+        let mut current_sizes: Vec<usize> = vec![];
+        for y in 0..(img.height()) {
+            for x in 0..(img.width()) {
+                if visited[(y * img.width() + x) as usize] || !self.is_link_color(img[(x, y)].to_rgb8()) {
+                    continue;
+                }
+                current_row.push(img[(x, y)].to_rgb8());
+                color_map.ensure_mapped(img[(x, y)].to_rgb8())?;
+                let filled = match self.pixel_mode {
+                    PixelMode::Blob => {
+                        let mut budget = self.max_region.saturating_add(1);
+                        let filled =
+                            flood_fill_region(img, &mut visited, (x, y), &self.is_separator, self.connectivity, &mut budget);
+                        if budget == 0 {
+                            return Err(format!(
+                                "The link at ({}, {}) covers more than {} pixels. The outline probably has a \
+                                 gap, or the outline color is wrong (--separator or --separator-at). Use \
+                                 --max-region to raise the limit.",
+                                x, y, self.max_region
+                            )
+                            .into());
+                        }
+                        filled
+                    }
+                    PixelMode::PerPixel => 1,
+                };
+                current_sizes.push(filled);
+            }
+            if !current_row.is_empty() {
+                rows.push(current_row);
+                current_row = vec![];
+                region_sizes.push(current_sizes);
+                current_sizes = vec![];
+            }
+        }
+        if rows.is_empty() {
+            return Err("No non-separator pixels found; check the separator color".into());
+        }
+        Ok(rows)
+    }
+}
+
+// Collapses a row into (color, count) runs of identical neighbouring links.
+fn rle_row(row: &[Rgb8]) -> Vec<(Rgb8, usize)> {
+    let mut runs: Vec<(Rgb8, usize)> = vec![];
+    for color in row {
+        match runs.last_mut() {
+            Some((last, count)) if last == color => *count += 1,
+            _ => runs.push((*color, 1)),
+        }
+    }
+    runs
+}
+
+// Flattens all links and re-chunks them into rows of `width`. The last row may be shorter.
+fn reflow(rows: Vec<Vec<Rgb8>>, width: usize) -> Vec<Vec<Rgb8>> {
+    let links: Vec<Rgb8> = rows.into_iter().flatten().collect();
+    links.chunks(width).map(|chunk| chunk.to_vec()).collect()
+}
+
+// Where each row starts when all links are laid end to end, for moving saved positions between
+// layouts of the same links.
+struct RowLayout {
+    // One start per row, then the total number of links.
+    starts: Vec<usize>,
+    mirrored: bool,
+}
+impl RowLayout {
+    fn new(rows: &[Vec<Rgb8>], mirrored: bool) -> RowLayout {
+        let mut starts = vec![0];
+        for row in rows {
+            starts.push(starts[starts.len() - 1] + row.len());
+        }
+        RowLayout { starts, mirrored }
+    }
+
+    fn row_count(&self) -> usize {
+        self.starts.len() - 1
+    }
+
+    fn row_len(&self, row: usize) -> usize {
+        self.starts[row + 1] - self.starts[row]
+    }
+
+    // The row holding link `index`, or None past the last link.
+    fn row_of(&self, index: usize) -> Option<usize> {
+        (index < self.starts[self.row_count()]).then(|| self.starts.partition_point(|start| *start <= index) - 1)
+    }
+
+    // How many links come before the position (row, col), where col counts woven links like
+    // `Progress` does.
+    fn woven_before(&self, (row, col): (usize, usize)) -> usize {
+        let row = row.min(self.row_count() - 1);
+        self.starts[row] + col.min(self.row_len(row))
+    }
+
+    // The position with `woven` links before it: the start of the next row after a finished one,
+    // or the end of the last row once everything is woven.
+    fn position_after(&self, woven: usize) -> (usize, usize) {
+        match self.row_of(woven) {
+            Some(row) => (row, woven - self.starts[row]),
+            None => {
+                let last = self.row_count() - 1;
+                (last, self.row_len(last))
+            },
+        }
+    }
+
+    // Where the link at (row, col) sits among all links, in the order the image has them.
+    fn link_index(&self, (row, col): (usize, usize)) -> Option<usize> {
+        let len = self.starts.get(row + 1)? - self.starts[row];
+        if col >= len {
+            return None;
+        }
+        Some(self.starts[row] + if self.mirrored { len - 1 - col } else { col })
+    }
+
+    fn link_at(&self, index: usize) -> Option<(usize, usize)> {
+        let row = self.row_of(index)?;
+        let col = index - self.starts[row];
+        Some((row, if self.mirrored { self.row_len(row) - 1 - col } else { col }))
+    }
+}
+
+// Applies the requested row layout and makes sure the saved progress still fits it. Progress,
+// notes and bookmarks saved against another `--reflow` width are moved onto the new rows first.
+fn arrange_rows(rows: Vec<Vec<Rgb8>>, reflow_width: Option<NonZeroUsize>, config: &mut Config) -> Vec<Vec<Rgb8>> {
+    let chunked = |width: Option<NonZeroUsize>| match width {
+        Some(width) => reflow(rows.clone(), width.get()),
+        None => rows.clone(),
+    };
+    if reflow_width != config.reflow && rows.iter().any(|row| !row.is_empty()) {
+        let saved = RowLayout::new(&chunked(config.reflow), config.mirror);
+        config.move_to_layout(&saved, &RowLayout::new(&chunked(reflow_width), config.mirror));
+    }
+    config.reflow = reflow_width;
+    let arranged = arranged_like(rows, config);
+    config.progress.clamp_to(&arranged);
+    arranged
+}
+
+// `rows` laid out like the pattern being woven, e.g. for comparing another image against it.
+fn arranged_like(rows: Vec<Vec<Rgb8>>, config: &Config) -> Vec<Vec<Rgb8>> {
+    let mut rows = match config.reflow {
+        Some(width) => reflow(rows, width.get()),
+        None => rows,
+    };
+    if config.mirror {
+        mirror_rows(&mut rows);
+    }
+    rows
+}
+
+// Whether `row` is drawn shifted half a link to the right: odd rows, or even ones when flipped.
+fn is_staggered(row: usize, flipped: bool) -> bool {
+    (row % 2 == 1) != flipped
+}
+
+// The TUI's stagger toggle flips which rows are shifted, and so does mirroring, since reversing a
+// row moves its offset to the other end.
+fn stagger_flipped(stagger_parity: bool, mirrored: bool) -> bool {
+    stagger_parity != mirrored
+}
+
+// Flips every row end to end, for weaving right to left.
+fn mirror_rows(rows: &mut [Vec<Rgb8>]) {
+    for row in rows {
+        row.reverse();
+    }
+}
+
+// The links the weave hasn't passed yet: each row in progress from its current link on (every
+// seed row during the seed phase) and all rows after. Finished rows are left out, except that the
+// result starts on an even row like pages do, so a finished row may lead it as an empty row and
+// odd rows keep their stagger. Empty if the pattern is finished.
+fn remaining_rows(rows: &[Vec<Rgb8>], progress: &Progress, seed_rows: usize) -> Vec<Vec<Rgb8>> {
+    let app = App::new(rows.to_vec(), progress.clone(), false, seed_rows, false, false);
+    let left: Vec<Vec<Rgb8>> = rows
+        .iter()
+        .enumerate()
+        .map(|(idx, row)| row[app.woven_in_row(idx)..].to_vec())
+        .collect();
+    match left.iter().position(|row| !row.is_empty()) {
+        Some(first) => left[first - first % 2..].to_vec(),
+        None => vec![],
+    }
+}
+
+// `row/total_rows col/row_len pct%` for the saved progress.
+fn status_line(rows: Vec<Vec<Rgb8>>, config: &Config) -> String {
+    let total_rows = rows.len();
+    let app = App::new(rows, config.progress.clone(), false, config.seed_rows, config.mirror, false);
+    let row_len = app.rows.get(app.progress.row).map(|r| r.len()).unwrap_or(0);
+    let percent = app.links_done() * 100 / app.total_links().max(1);
+    let (row, col) = app.display_position();
+    format!("{}/{} {}/{} {}%", row, total_rows, col, row_len, percent)
+}
+
+// Every link that changed color between two parses, as (row, col, old, new). Only positions
+// present in both are compared; differences in row count or length are left to the caller.
+fn diff_rows(old: &[Vec<Rgb8>], new: &[Vec<Rgb8>]) -> Vec<(usize, usize, Rgb8, Rgb8)> {
+    old.iter()
+        .zip(new.iter())
+        .enumerate()
+        .flat_map(|(row_idx, (old_row, new_row))| {
+            old_row
+                .iter()
+                .zip(new_row.iter())
+                .enumerate()
+                .filter(|(_, (old, new))| old != new)
+                .map(move |(col_idx, (old, new))| (row_idx, col_idx, *old, *new))
+        })
+        .collect()
+}
+
+fn print_diff(old: &[Vec<Rgb8>], new: &[Vec<Rgb8>], color_map: &ColorMap) {
+    let changes = diff_rows(old, new);
+    for (row, col, old_color, new_color) in &changes {
+        println!(
+            "Row {}, link {}: {} ({}) -> {} ({})",
+            row + 1,
+            col + 1,
+            color_map.full_name(*old_color).unwrap_or(UNMAPPED_NAME),
+            old_color.to_hex(),
+            color_map.full_name(*new_color).unwrap_or(UNMAPPED_NAME),
+            new_color.to_hex(),
+        );
+    }
+    if old.len() != new.len() {
+        println!("Row count changed: {} -> {}", old.len(), new.len());
+    }
+    for (row_idx, (old_row, new_row)) in old.iter().zip(new.iter()).enumerate() {
+        if old_row.len() != new_row.len() {
+            println!("Row {} length changed: {} -> {}", row_idx + 1, old_row.len(), new_row.len());
+        }
+    }
+    println!("{} links changed", changes.len());
+}
+
+// Summarizes how many pixels each link covered and lists the ones far above the median, which
+// usually means two links bled into each other through a gap in the outline.
+fn print_region_stats(rows: &[Vec<Rgb8>], sizes: &[Vec<usize>], color_map: &ColorMap) {
+    let mut sorted: Vec<usize> = sizes.iter().flatten().copied().collect();
+    if sorted.is_empty() {
+        println!("No links found");
+        return;
+    }
+    sorted.sort_unstable();
+    let median = sorted[sorted.len() / 2];
+    println!(
+        "Region sizes: min {}, median {}, max {} pixels",
+        sorted[0],
+        median,
+        sorted[sorted.len() - 1]
+    );
+
+    let mut outliers = 0;
+    for (row_idx, (row, row_sizes)) in rows.iter().zip(sizes).enumerate() {
+        for (col_idx, (color, size)) in row.iter().zip(row_sizes).enumerate() {
+            if *size > median * REGION_SIZE_OUTLIER_RATIO {
+                outliers += 1;
+                println!(
+                    "Row {}, link {}: {} ({}) covers {} pixels",
+                    row_idx + 1,
+                    col_idx + 1,
+                    color_map.full_name(*color).unwrap_or(UNMAPPED_NAME),
+                    color.to_hex(),
+                    size
+                );
+            }
+        }
+    }
+    println!(
+        "{} links larger than {}x the median",
+        outliers, REGION_SIZE_OUTLIER_RATIO
+    );
+}
+
+// Index `n` holds how many rows have exactly `n` links.
+fn row_length_histogram(rows: &[Vec<Rgb8>]) -> Vec<usize> {
+    let longest = rows.iter().map(|r| r.len()).max().unwrap_or(0);
+    let mut histogram = vec![0; longest + 1];
+    for row in rows {
+        histogram[row.len()] += 1;
+    }
+    histogram
+}
+
+// Wildly varying row lengths usually mean the separator color is wrong, so give the user a
+// chance to bail out before they start weaving.
+fn warn_on_row_length_anomalies(rows: &[Vec<Rgb8>]) -> Result<(), Box<dyn Error>> {
+    use io::Write;
+
+    if rows.is_empty() {
+        return Ok(());
+    }
+    let histogram = row_length_histogram(rows);
+    let row_count = rows.len() as f64;
+    let mean = histogram
+        .iter()
+        .enumerate()
+        .map(|(len, count)| (len * count) as f64)
+        .sum::<f64>()
+        / row_count;
+    let variance = histogram
+        .iter()
+        .enumerate()
+        .map(|(len, count)| (len as f64 - mean).powi(2) * *count as f64)
+        .sum::<f64>()
+        / row_count;
+    let std_dev = variance.sqrt();
+    if std_dev <= mean * ROW_LENGTH_WARN_RATIO {
+        return Ok(());
+    }
+
+    println!(
+        "Warning: row lengths vary a lot (mean {:.1}, std dev {:.1}). The outline may not have been detected correctly.",
+        mean, std_dev
+    );
+    println!("Row lengths (links: rows):");
+    for (len, count) in histogram.iter().enumerate().filter(|(_, count)| **count > 0) {
+        println!("  {}: {}", len, count);
+    }
+    print!("Press Enter to continue anyway, or Ctrl-C to quit: ");
+    io::stdout().flush()?;
+    io::stdin().read_line(&mut String::new())?;
+    Ok(())
+}
+
+// How a color map lines up with the colors actually in an image.
+struct VerifyReport {
+    // In the image but not the map, in the order they were found.
+    unmapped: Vec<Rgb8>,
+    // In the map but not the image, sorted by name.
+    unused: Vec<(Rgb8, String)>,
+}
+
+fn verify(colors: &[Rgb8], color_map: &ColorMap) -> VerifyReport {
+    VerifyReport {
+        unmapped: colors.iter().copied().filter(|color| !color_map.is_mapped(*color)).collect(),
+        unused: color_map
+            .sorted_entries()
+            .into_iter()
+            .filter(|(color, _, _)| !colors.contains(color))
+            .map(|(color, full_name, _)| (color, full_name.to_owned()))
+            .collect(),
+    }
+}
+
+// `--config`: prints a saved color map, or writes it as a palette, without touching an image.
+fn color_map_only(config_path: &Path, export_palette: Option<&Path>) -> Result<(), Box<dyn Error>> {
+    let config = Config::from_file(config_path)?;
+    if let Some(path) = export_palette {
+        config.color_map.export_gpl(path)?;
+        println!("Wrote {}", path.display());
+        return Ok(());
+    }
+    for (color, full_name, one_char) in config.color_map.sorted_entries() {
+        println!("  {} {} {}", one_char, color.to_hex(), full_name);
+    }
+    Ok(())
+}
+
+// Like ImageReader's open and decode, but errors say which file failed and why.
+fn load_image(path: &Path) -> Result<RgbImage, Box<dyn Error>> {
+    let reader = ImageReader::open(path).map_err(|e| format!("Could not open {}: {}", path.display(), e))?;
+    let img = reader.decode().map_err(|e| {
+        let hint = match e {
+            image::ImageError::Unsupported(_) => {
+                ". Save the pattern as a PNG, BMP or GIF; lossless formats keep link colors exact"
+            }
+            _ => "",
+        };
+        format!("Could not read {} as an image: {}{}", path.display(), e, hint)
+    })?;
+    Ok(img.to_rgb8())
+}
+
+// The palette of an indexed PNG or GIF, or None for any other image.
+fn read_palette(path: &Path) -> Result<Option<Vec<Rgb8>>, Box<dyn Error>> {
+    let to_colors = |bytes: &[u8]| {
+        bytes
+            .chunks_exact(3)
+            .map(|c| Rgb8([c[0], c[1], c[2]]))
+            .collect::<Vec<_>>()
+    };
+    match image::ImageFormat::from_path(path) {
+        Ok(image::ImageFormat::Png) => {
+            let reader = png::Decoder::new(fs::File::open(path)?).read_info()?;
+            let info = reader.info();
+            if info.color_type != png::ColorType::Indexed {
+                return Ok(None);
+            }
+            Ok(info.palette.as_deref().map(to_colors))
+        }
+        Ok(image::ImageFormat::Gif) => {
+            let decoder = gif::DecodeOptions::new().read_info(fs::File::open(path)?)?;
+            Ok(decoder.global_palette().map(to_colors))
+        }
+        _ => Ok(None),
+    }
+}
+
+// The smallest (x, y, width, height) rectangle holding every non-separator pixel, or None if the
+// image is all separator.
+fn content_bounds(img: &RgbImage, is_separator: impl Fn(Rgb8) -> bool) -> Option<(u32, u32, u32, u32)> {
+    let (mut left, mut top, mut right, mut bottom) = (u32::MAX, u32::MAX, 0, 0);
+    for (x, y, pixel) in img.enumerate_pixels() {
+        if !is_separator(pixel.to_rgb8()) {
+            left = left.min(x);
+            top = top.min(y);
+            right = right.max(x);
+            bottom = bottom.max(y);
+        }
+    }
+    (left <= right).then(|| (left, top, right - left + 1, bottom - top + 1))
+}
+
+// Merges colors that are within `max_distance` of each other on every channel, so that compression
+// noise doesn't turn one bead color into dozens. The most common color of each cluster is kept as
+// its representative. Separator pixels are left untouched. Returns how many colors were merged away.
+fn quantize_colors(img: &mut RgbImage, max_distance: u8, is_separator: &dyn Fn(Rgb8) -> bool) -> usize {
+    let mut counts: HashMap<Rgb8, usize> = HashMap::new();
+    for pixel in img.pixels() {
+        let color = pixel.to_rgb8();
+        if !is_separator(color) {
+            *counts.entry(color).or_default() += 1;
+        }
+    }
+    let mut by_frequency: Vec<(Rgb8, usize)> = counts.into_iter().collect();
+    by_frequency.sort_by(|(a_color, a_count), (b_color, b_count)| {
+        b_count.cmp(a_count).then(a_color.0.cmp(&b_color.0))
+    });
+
+    let is_close = |a: Rgb8, b: Rgb8| a.distance(b) <= max_distance;
+    let mut representatives: Vec<Rgb8> = vec![];
+    let mut replacements: HashMap<Rgb8, Rgb8> = HashMap::new();
+    for (color, _) in by_frequency {
+        match representatives.iter().find(|rep| is_close(**rep, color)) {
+            Some(rep) => {
+                replacements.insert(color, *rep);
+            }
+            None => representatives.push(color),
+        }
+    }
+
+    if !replacements.is_empty() {
+        for pixel in img.pixels_mut() {
+            if let Some(rep) = replacements.get(&pixel.to_rgb8()) {
+                *pixel = Rgb(rep.0);
+            }
+        }
+    }
+    replacements.len()
+}
+
+fn setup_tui() -> Result<Terminal<impl Backend + io::Write>, Box<dyn Error>> {
+    enable_raw_mode()?;
+    let stdout = io::stdout();
+    let mut backend = CrosstermBackend::new(stdout);
+    execute!(backend, EnterAlternateScreen, EnableMouseCapture)?;
+    backend.hide_cursor()?;
+    Ok(Terminal::new(backend)?)
+}
+
+fn teardown_tui() -> Result<(), Box<dyn Error>> {
+    disable_raw_mode()?;
+    execute!(
+        io::stdout(),
+        LeaveAlternateScreen,
+        DisableMouseCapture
+    )?;
+
+    Ok(())
+}
+// Restores the terminal when dropped, so every early return after setup_tui cleans up too.
+struct TuiGuard;
+
+impl Drop for TuiGuard {
+    fn drop(&mut self) {
+        let _ = teardown_tui();
+    }
+}
+
+// A lock file holding our pid, so a second instance of the same pattern refuses to start instead
+// of overwriting this one's progress. Removed on drop.
+struct ProgressLock {
+    path: PathBuf,
+}
+
+impl ProgressLock {
+    fn acquire(path: PathBuf, force: bool) -> Result<ProgressLock, Box<dyn Error>> {
+        use io::Write;
+        let mut options = fs::OpenOptions::new();
+        options.write(true);
+        if force {
+            options.create(true).truncate(true);
+        } else {
+            options.create_new(true);
+        }
+        let mut file = match options.open(&path) {
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+                let owner = fs::read_to_string(&path).unwrap_or_default();
+                return Err(format!(
+                    "This pattern is already open in another instance (pid {}). If that instance \
+                     is gone, e.g. after a crash, pass --force to open it anyway.",
+                    owner.trim()
+                )
+                .into());
+            }
+            result => result?,
+        };
+        write!(file, "{}", std::process::id())?;
+        Ok(ProgressLock { path })
+    }
+}
+
+impl Drop for ProgressLock {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+// Panics unwind through TuiGuard as well, but the hook restores the terminal before the panic
+// message is printed, so it isn't swallowed by the alternate screen.
+fn init_panic_hook() {
+    use std::panic::{set_hook, take_hook};
+    let original_hook = take_hook();
+    set_hook(Box::new(move |panic_info| {
+        let _ = teardown_tui();
         original_hook(panic_info);
     }));
 }
 
+// Decides when the TUI writes progress to disk: after every `every` links, or once
+// AUTOSAVE_INTERVAL has passed with unsaved changes.
+struct Autosave {
+    every: usize,
+    links_at_save: usize,
+    last_save: Instant,
+}
+
+impl Autosave {
+    fn new(every: usize, links_done: usize) -> Autosave {
+        Autosave { every, links_at_save: links_done, last_save: Instant::now() }
+    }
+
+    fn is_due(&self, links_done: usize, changed: bool) -> bool {
+        let links = links_done.abs_diff(self.links_at_save);
+        changed
+            && ((self.every > 0 && links >= self.every) || self.last_save.elapsed() >= AUTOSAVE_INTERVAL)
+    }
+
+    fn saved(&mut self, links_done: usize) {
+        self.links_at_save = links_done;
+        self.last_save = Instant::now();
+    }
+}
+
 fn run_app(
     term: &mut Terminal<impl Backend>,
     config: &mut Config,
     rows: Vec<Vec<Rgb8>>,
+    source_preview: SourcePreview,
+    progress_json: Option<PathBuf>,
+    play_interval: Duration,
+) -> Result<(), Box<dyn Error>> {
+    let mut app = App::new(
+        rows,
+        config.progress.clone(),
+        config.pause_on_row_complete,
+        config.seed_rows,
+        config.mirror,
+        config.auto_mark_rows,
+    );
+    let mut ui_state = UIState::new(&app, config.stagger_parity, config.scroll);
+    ui_state.source_preview = Some(source_preview);
+    ui_state.controls_help = controls_help(&config.keymap);
+    let result = run_loop(term, config, &mut app, &mut ui_state, progress_json, play_interval);
+    // However the loop ended, even on an error, hand what the session changed back to the caller.
+    config.progress = app.progress;
+    config.stagger_parity = ui_state.stagger_parity;
+    config.scroll = Some((ui_state.vertical_scroll_amount, ui_state.saved_horizontal_scroll()));
+    result
+}
+
+// The TUI's event loop, until the user quits or something fails.
+fn run_loop(
+    term: &mut Terminal<impl Backend>,
+    config: &mut Config,
+    app: &mut App,
+    ui_state: &mut UIState,
+    progress_json: Option<PathBuf>,
+    play_interval: Duration,
 ) -> Result<(), Box<dyn Error>> {
-    let mut app = App::new(rows, &mut config.progress);
-    let mut ui_state = UIState::new(&app);
-    let tick_rate = Duration::from_millis(250);
+    let mut autosave = Autosave::new(config.autosave_every, app.links_done());
+    // Set by edits to the config that aren't weave progress, e.g. color names, notes and bookmarks.
+    let mut config_edited = false;
+    // The session time, links per minute and ETA change with time alone, and count whole minutes,
+    // so an idle session repaints for them only this often.
+    let tick_rate = Duration::from_secs(5);
     let mut last_tick = Instant::now();
+    let mut last_play_tick = Instant::now();
+
+    // Only redraw after something changed, so an idle session doesn't repaint a large pattern
+    // several times a second (which flickers over SSH).
+    let mut needs_redraw = true;
 
     loop {
-        term.draw(|f| ui(f, &mut app, &mut ui_state, &config.color_map))?;
+        if app.playing && last_play_tick.elapsed() >= play_interval {
+            last_play_tick = Instant::now();
+            if app.is_done() {
+                app.playing = false;
+            } else if !app.just_completed_row {
+                app.tick();
+            }
+            needs_redraw = true;
+        }
+        // A color can go missing from the map mid-session, e.g. by undoing past its addition.
+        if let Some(color) = app.current_color().filter(|color| {
+            ui_state.input.is_none()
+                && !config.color_map.is_mapped(*color)
+                && !ui_state.dismissed_unmapped.contains(color)
+        }) {
+            ui_state.input = Some(TextInput {
+                purpose: InputPurpose::ColorName(color),
+                buffer: String::new(),
+            });
+            needs_redraw = true;
+        }
+        if autosave.is_due(app.links_done(), config_edited || app.progress != config.progress) {
+            config_edited = false;
+            config.progress = app.progress.clone();
+            config.save()?;
+            // A failed write only leaves the overlay behind, which isn't worth ending the session over.
+            if let Some(path) = &progress_json {
+                if let Err(e) = app.write_progress_json(path) {
+                    ui_state.warning = Some(format!("Could not write {}: {}", path.display(), e));
+                }
+            }
+            autosave.saved(app.links_done());
+        }
+        if needs_redraw {
+            term.draw(|f| ui(f, app, ui_state, config))?;
+            needs_redraw = false;
+        }
 
-        let timeout = tick_rate.saturating_sub(last_tick.elapsed());
+        let mut timeout = tick_rate.saturating_sub(last_tick.elapsed());
+        if app.playing {
+            timeout = timeout.min(play_interval.saturating_sub(last_play_tick.elapsed()));
+        }
         if crossterm::event::poll(timeout)? {
-            if let Event::Key(key) = event::read()? {
+            let event = event::read()?;
+            if let Event::Resize(..) = event {
+                needs_redraw = true;
+            }
+            if let Event::Key(key) = event {
                 if key.kind != KeyEventKind::Press {
                     continue;
                 }
-                match key.code {
-                    KeyCode::Char('q') => return Ok(()),
-                    KeyCode::Left | KeyCode::Char('h') => {
-                        if ui_state.horizontal_scroll_amount > 0 {
-                            ui_state.horizontal_scroll_amount -= 1
+                needs_redraw = true;
+                ui_state.warning = None;
+                if let Some(mut input) = ui_state.input.take() {
+                    match key.code {
+                        KeyCode::Esc => {
+                            if let Some(color) = input.color().filter(|c| !config.color_map.is_mapped(*c)) {
+                                ui_state.dismissed_unmapped.insert(color);
+                            }
+                        },
+                        KeyCode::Backspace if input.buffer.is_empty() => ui_state.input = Some(input.back()),
+                        KeyCode::Backspace => {
+                            input.buffer.pop();
+                            ui_state.input = Some(input);
+                        },
+                        KeyCode::Char(c) => {
+                            input.buffer.push(c);
+                            ui_state.input = Some(input);
+                        },
+                        KeyCode::Enter => {
+                            ui_state.input = submit_input(input, app, &mut config.color_map, &mut config.notes);
+                            config_edited = true;
+                        },
+                        _ => ui_state.input = Some(input),
+                    }
+                    continue;
+                }
+                let action = key_name(key.code).and_then(|name| config.keymap.get(&name)).copied();
+                // The quit key again quits; any other key stays.
+                let quit_confirmed = std::mem::take(&mut ui_state.confirming_quit);
+                if quit_confirmed && action != Some(Action::Quit) {
+                    continue;
+                }
+                if let Some(selected) = ui_state.color_list {
+                    let entries = config.color_map.sorted_entries();
+                    // Esc and Enter always work here; everything else goes through the keymap.
+                    let action = match key.code {
+                        KeyCode::Esc => Some(Action::ColorList),
+                        KeyCode::Enter => Some(Action::FocusColor),
+                        _ => action,
+                    };
+                    match action {
+                        Some(Action::ColorList) | Some(Action::Quit) => ui_state.color_list = None,
+                        Some(Action::ScrollDown) => {
+                            ui_state.color_list = Some((selected + 1).min(entries.len().saturating_sub(1)))
+                        },
+                        Some(Action::ScrollUp) => ui_state.color_list = Some(selected.saturating_sub(1)),
+                        Some(Action::FocusColor) => {
+                            if let Some((color, _, _)) = entries.get(selected) {
+                                ui_state.toggle_focus(*color);
+                            }
+                        },
+                        Some(Action::ClearFocus) => ui_state.focused_colors.clear(),
+                        Some(Action::ToggleLinkDone) => {
+                            if let Some((color, _, _)) = entries.get(selected) {
+                                if !config.completed_colors.remove(color) {
+                                    config.completed_colors.insert(*color);
+                                }
+                                config_edited = true;
+                            }
+                        },
+                        _ => {},
+                    }
+                    continue;
+                }
+                if let Some(action) = ui_state.pending_bookmark.take() {
+                    if let KeyCode::Char(name) = key.code {
+                        match action {
+                            BookmarkAction::Set => {
+                                let mark = Progress {
+                                    completed: HashSet::new(),
+                                    rows_done: vec![],
+                                    ..app.progress.clone()
+                                };
+                                config.bookmarks.insert(name.to_string(), mark);
+                                config_edited = true;
+                            },
+                            BookmarkAction::Jump => {
+                                if let Some(mark) = config.bookmarks.get(&name.to_string()) {
+                                    app.jump_to(mark);
+                                }
+                            },
                         }
+                    }
+                    continue;
+                }
+                // A vim-style count before a key repeats it, e.g. `30 Space` or `5j`.
+                if let KeyCode::Char(digit @ '0'..='9') = key.code {
+                    if digit != '0' || ui_state.count.is_some() {
+                        let digit = digit.to_digit(10).unwrap() as usize;
+                        let count = ui_state.count.unwrap_or(0);
+                        ui_state.count = Some(count.saturating_mul(10).saturating_add(digit));
+                        continue;
+                    }
+                }
+                let count = ui_state.count.take().unwrap_or(1);
+                match action {
+                    Some(Action::Quit) if config.confirm_quit && !quit_confirmed => ui_state.confirming_quit = true,
+                    Some(Action::Quit) => {
+                        // The caller still saves the config before reporting a failed write.
+                        if let Some(path) = &progress_json {
+                            app.write_progress_json(path)
+                                .map_err(|e| format!("Could not write {}: {}", path.display(), e))?;
+                        }
+                        return Ok(());
                     },
-                    KeyCode::Down | KeyCode::Char('j') => ui_state.vertical_scroll_amount += 1,
-                    KeyCode::Up | KeyCode::Char('k') => {
-                        if ui_state.vertical_scroll_amount > 0 {
-                            ui_state.vertical_scroll_amount -= 1
+                    Some(Action::ToggleStagger) => ui_state.stagger_parity = !ui_state.stagger_parity,
+                    Some(Action::SetBookmark) => ui_state.pending_bookmark = Some(BookmarkAction::Set),
+                    Some(Action::JumpToBookmark) => ui_state.pending_bookmark = Some(BookmarkAction::Jump),
+                    Some(Action::ToggleMirror) => {
+                        app.toggle_mirror();
+                        config.mirror = app.mirrored;
+                        config.mirror_links(&app.rows);
+                        config_edited = true;
+                    },
+                    Some(Action::ScrollTop) => ui_state.vertical_scroll_amount = 0,
+                    Some(Action::ScrollBottom) => ui_state.scroll_to_bottom(app),
+                    Some(Action::ScrollRowStart) => ui_state.horizontal_scroll_amount = 0,
+                    Some(Action::ScrollRowEnd) => ui_state.scroll_to_row_end(app),
+                    Some(Action::GoToCurrent) => app.ensure_current_on_screen = true,
+                    Some(Action::FocusColor) => {
+                        if let Some(color) = app.current_color() {
+                            ui_state.toggle_focus(color);
                         }
                     },
-                    KeyCode::Right | KeyCode::Char('l') => ui_state.horizontal_scroll_amount += 1,
-                    KeyCode::Char('r') => {
-                        app.reset();
+                    Some(Action::ClearFocus) => ui_state.focused_colors.clear(),
+                    Some(Action::ColorList) => ui_state.color_list = Some(0),
+                    Some(Action::ToggleSplit) => ui_state.toggle_split(),
+                    Some(Action::SwitchPane) => ui_state.switch_pane(),
+                    Some(Action::ToggleSourceImage) => {
+                        if let Some(preview) = ui_state.source_preview.as_mut() {
+                            preview.visible = !preview.visible;
+                            preview.show_outlines = false;
+                        }
                     },
-                    KeyCode::Char(' ') => {
-                        if !app.is_done() {
-                            app.tick()
+                    Some(Action::ToggleOutlines) => {
+                        if let Some(preview) = ui_state.source_preview.as_mut() {
+                            preview.toggle_outlines();
                         }
                     },
-                    KeyCode::Char('P') => { for _ in 0..30 { app.tick();} },
+                    Some(Action::AutoPlay) => {
+                        app.playing = !app.playing && !app.is_done();
+                        last_play_tick = Instant::now();
+                    },
+                    Some(Action::ScrollLeft) => {
+                        ui_state.horizontal_scroll_amount = ui_state.horizontal_scroll_amount.saturating_sub(count)
+                    },
+                    Some(Action::ScrollDown) => {
+                        ui_state.vertical_scroll_amount = ui_state
+                            .vertical_scroll_amount
+                            .saturating_add(count)
+                            .min(UIState::max_vertical_scroll(app))
+                    },
+                    Some(Action::ScrollUp) => {
+                        ui_state.vertical_scroll_amount = ui_state.vertical_scroll_amount.saturating_sub(count)
+                    },
+                    Some(Action::ScrollRight) => {
+                        ui_state.horizontal_scroll_amount = ui_state
+                            .horizontal_scroll_amount
+                            .saturating_add(count)
+                            .min(ui_state.max_horizontal_scroll(app))
+                    },
+                    Some(Action::Reset) => {
+                        app.reset();
+                    },
+                    Some(Action::Next) if app.just_completed_row => app.just_completed_row = false,
+                    None if key.code == KeyCode::Enter && app.just_completed_row => app.just_completed_row = false,
+                    Some(Action::Next) => app.advance(count),
+                    Some(Action::ToggleLinkDone) => app.toggle_completed(),
+                    Some(Action::ToggleRowDone) => app.toggle_row_done(),
+                    Some(Action::TakeBreak) => app.links_since_break = 0,
+                    Some(Action::ToggleRuns) => ui_state.show_runs = !ui_state.show_runs,
+                    Some(Action::ToggleFullNames) => ui_state.toggle_full_names(),
+                    Some(Action::ToggleIndices) => ui_state.show_indices = !ui_state.show_indices,
+                    Some(Action::EditColor) => {
+                        ui_state.input = app.current_color().map(|color| TextInput {
+                            purpose: InputPurpose::ColorName(color),
+                            buffer: config.color_map.full_name(color).unwrap_or_default().to_owned(),
+                        });
+                    },
+                    Some(Action::EditNote) => {
+                        ui_state.input = app.current_link().map(|link| TextInput {
+                            purpose: InputPurpose::Note(link),
+                            buffer: config.notes.get(&link).cloned().unwrap_or_default(),
+                        });
+                    },
+                    Some(Action::JumpToRow) => {
+                        ui_state.input = Some(TextInput {
+                            purpose: InputPurpose::JumpToRow,
+                            buffer: String::new(),
+                        });
+                    },
+                    Some(Action::Undo) => config_edited |= config.color_map.undo(),
+                    Some(Action::Redo) => config_edited |= config.color_map.redo(),
+                    Some(Action::SkipAhead) if !app.just_completed_row => app.advance(30),
                     _ => {},
                 }
                 // handle input
@@ -413,30 +2976,133 @@ fn run_app(
         }
         if last_tick.elapsed() >= tick_rate {
             last_tick = Instant::now();
+            needs_redraw = true;
+        }
+    }
+}
+
+// Handles Enter in the instruction line, returning the follow-up input if there is one.
+fn submit_input(
+    input: TextInput,
+    app: &mut App,
+    color_map: &mut ColorMap,
+    notes: &mut HashMap<(usize, usize), String>,
+) -> Option<TextInput> {
+    let text = input.buffer.trim();
+    match input.purpose {
+        InputPurpose::Note(link) => {
+            if text.is_empty() {
+                notes.remove(&link);
+            } else {
+                notes.insert(link, text.to_owned());
+            }
+            None
         }
+        InputPurpose::JumpToRow => match parse_jump_target(text, &app.rows) {
+            Some((row, col)) => {
+                app.jump_to(&Progress { row, col, ..app.progress.clone() });
+                None
+            }
+            None => Some(TextInput { purpose: InputPurpose::JumpToRow, buffer: String::new() }),
+        },
+        InputPurpose::ColorName(color) if !text.is_empty() => Some(TextInput {
+            purpose: InputPurpose::ColorChar(color, text.to_owned()),
+            buffer: color_map.one_char(color).unwrap_or_default().to_owned(),
+        }),
+        InputPurpose::ColorChar(color, full_name) => {
+            match text.chars().next() {
+                Some(one_char) => {
+                    color_map.add_entry(color, &full_name, &one_char.to_string());
+                    None
+                }
+                None => Some(TextInput {
+                    purpose: InputPurpose::ColorChar(color, full_name),
+                    buffer: String::new(),
+                }),
+            }
+        }
+        purpose => Some(TextInput { purpose, buffer: String::new() }),
     }
 }
 
-fn ui(f: &mut Frame, app: &mut App, ui_state: &mut UIState, color_map: &ColorMap) {
-    use ratatui::widgets::canvas::{Canvas, Rectangle, Map, MapResolution};
+// Parses the jump prompt's 1-based `row` or `row,link` into a (row, col) position where that
+// link is the current one. None if it's malformed or past the end of the pattern.
+fn parse_jump_target(text: &str, rows: &[Vec<Rgb8>]) -> Option<(usize, usize)> {
+    let (row, link) = match text.split_once(',') {
+        Some((row, link)) => (row.trim().parse::<usize>().ok()?, link.trim().parse::<usize>().ok()?),
+        None => (text.parse::<usize>().ok()?, 0),
+    };
+    let row_len = rows.get(row.checked_sub(1)?)?.len();
+    (link <= row_len).then_some((row - 1, link))
+}
+
+fn ui(
+    f: &mut Frame,
+    app: &mut App,
+    ui_state: &mut UIState,
+    config: &Config,
+) {
+    use ratatui::widgets::canvas::Canvas;
     use NextPreview::*;
+    let Config { color_map, bookmarks, notes, .. } = config;
+
+    let size = f.size();
+    let (min_width, min_height) = MIN_TERMINAL_SIZE;
+    if size.width < min_width || size.height < min_height {
+        let [_, message_area, _] =
+            Layout::vertical([Constraint::Fill(1), Constraint::Length(2), Constraint::Fill(1)]).areas(size);
+        let message = Paragraph::new(format!(
+            "Terminal too small: {}x{}, need at least {}x{}",
+            size.width, size.height, min_width, min_height
+        ))
+        .alignment(Alignment::Center)
+        .wrap(Wrap { trim: true });
+        f.render_widget(message, message_area);
+        return;
+    }
 
     let main_layout = Layout::vertical([
         Constraint::Percentage(70),
         Constraint::Percentage(30),
         Constraint::Min(1),
     ]);
-    let [image_frame, color_frame, instruction_line] = main_layout.areas(f.size());
-    let colors_layout = Layout::horizontal([Constraint::Ratio(1, 2), Constraint::Ratio(1, 2)]);
-    let [current_color_box, next_color_box] = colors_layout.areas(color_frame);
-    let tri_box_layout = Layout::vertical([Constraint::Ratio(1, 3), Constraint::Ratio(1, 3), Constraint::Ratio(1, 3)]);
+    let [image_frame, color_frame, instruction_line] = main_layout.areas(size);
+    let colors_layout = Layout::horizontal([
+        Constraint::Ratio(1, 3),
+        Constraint::Ratio(1, 3),
+        Constraint::Ratio(1, 3),
+    ]);
+    let [prev_color_box, current_color_box, next_color_box] = colors_layout.areas(color_frame);
+    let (pattern_frame, other_pattern_frame) = match ui_state.split {
+        Some(split) => {
+            let [left, right] =
+                Layout::horizontal([Constraint::Ratio(1, 2), Constraint::Ratio(1, 2)]).areas(image_frame);
+            if split.active_right {
+                (right, Some((left, split.other_scroll)))
+            } else {
+                (left, Some((right, split.other_scroll)))
+            }
+        }
+        None => (image_frame, None),
+    };
+    // A ruler line above each pane, lined up with the inside of its border.
+    let split_ruler = |area: Rect| {
+        let [ruler, pane] = Layout::vertical([Constraint::Length(1), Constraint::Fill(1)]).areas(area);
+        (ruler.inner(&Margin { vertical: 0, horizontal: 1 }), pane)
+    };
+    let (ruler_frame, pattern_frame) = split_ruler(pattern_frame);
+    let other_pattern_frame = other_pattern_frame.map(|(frame, scroll)| (split_ruler(frame), scroll));
 
+    ui_state.view_size = (
+        pattern_frame.height.saturating_sub(2) as usize,
+        pattern_frame.width.saturating_sub(2) as usize,
+    );
     {
         if app.ensure_current_on_screen {
             // vertical
             {
                 // Subtract 2 because we use 2 chars for the border
-                let frame_size = image_frame.height as usize - 2;
+                let frame_size = pattern_frame.height.saturating_sub(2) as usize;
                 let content_length = app.lines.len();
                 // Add 1 because we can't see whats behind the top-most border
                 let current_scroll = ui_state.vertical_scroll_amount + 1;
@@ -446,8 +3112,9 @@ fn ui(f: &mut Frame, app: &mut App, ui_state: &mut UIState, color_map: &ColorMap
             // horizontal
             {
                 // Subtract 2 because we use 2 chars for the border
-                let frame_size = image_frame.width as usize - 2;
-                let content_length = app.lines.last().map(|l| l.len()).unwrap_or(0) * 2;
+                let frame_size = pattern_frame.width.saturating_sub(2) as usize;
+                let content_length =
+                    ROW_GUTTER_WIDTH + app.lines.last().map(|l| l.len()).unwrap_or(0) * ui_state.cell_period();
                 // Add 1 because we can't see whats behind the left-most border
                 let current_scroll = ui_state.horizontal_scroll_amount + 1;
                 // Subtract 1 to account for the 1 we added earlier
@@ -465,15 +3132,37 @@ fn ui(f: &mut Frame, app: &mut App, ui_state: &mut UIState, color_map: &ColorMap
         .iter()
         .enumerate()
         .map(|(row_idx, row)| {
-            let mut line = row.iter()
-                .map(|c| {
-                    Span::styled(color_map.one_char(*c), Color::Rgb(c.0[0], c.0[1], c.0[2]))
-                })
-                .intersperse(Span::raw(" "))
-                .collect::<Vec<_>>();
-            if row_idx % 2 == 1 {
-                line.insert(0, Span::raw(" "));
+            let mut line = Itertools::intersperse(
+                row.iter().enumerate().map(|(col_idx, c)| {
+                    let mut style = Style::new().fg(Color::Rgb(c.0[0], c.0[1], c.0[2]));
+                    if app.progress.completed.contains(&(row_idx, col_idx)) {
+                        style = style.add_modifier(Modifier::DIM | Modifier::CROSSED_OUT);
+                    }
+                    if notes.contains_key(&(row_idx, col_idx)) {
+                        style = style.add_modifier(Modifier::UNDERLINED);
+                    }
+                    if !ui_state.focused_colors.is_empty() {
+                        style = if ui_state.focused_colors.contains(c) {
+                            style.add_modifier(Modifier::BOLD)
+                        } else {
+                            style.add_modifier(Modifier::DIM)
+                        };
+                    }
+                    if ui_state.full_names {
+                        let name = color_map.full_name(*c).unwrap_or(UNMAPPED_NAME);
+                        Span::styled(format!("{:<width$.width$}", name, width = NAME_CELL_WIDTH), style)
+                    } else {
+                        Span::styled(color_map.one_char(*c).unwrap_or(UNMAPPED_CHAR), style)
+                    }
+                }),
+                Span::raw(" "),
+            )
+            .collect::<Vec<_>>();
+            if is_staggered(row_idx, stagger_flipped(ui_state.stagger_parity, app.mirrored)) {
+                line.insert(0, Span::raw(" ".repeat(ui_state.cell_period() / 2)));
             }
+            let check = if app.progress.is_row_done(row_idx) { "\u{2713}" } else { " " };
+            line.insert(0, Span::styled(format!("{:<width$}", check, width = ROW_GUTTER_WIDTH), Style::new().green()));
             Line::from(line)
         })
         .collect::<Vec<_>>();
@@ -483,31 +3172,87 @@ fn ui(f: &mut Frame, app: &mut App, ui_state: &mut UIState, color_map: &ColorMap
         .position(ui_state.vertical_scroll_amount);
     ui_state.horizontal_scroll = ui_state.horizontal_scroll.position(ui_state.horizontal_scroll_amount);
 
-    let para = Paragraph::new(text).block(create_block("Pattern")).scroll((
-        ui_state.vertical_scroll_amount as u16,
-        ui_state.horizontal_scroll_amount as u16,
-    ));
-    f.render_widget(para, image_frame);
-    f.render_stateful_widget(
-        Scrollbar::new(ScrollbarOrientation::HorizontalBottom),
-        image_frame.inner(&Margin {
-            vertical: 0,
-            horizontal: 1,
-        }),
-        &mut ui_state.horizontal_scroll,
-    );
-    f.render_stateful_widget(
-        Scrollbar::new(ScrollbarOrientation::VerticalRight),
-        image_frame.inner(&Margin {
-            vertical: 1,
-            horizontal: 0,
-        }),
-        &mut ui_state.vertical_scroll,
-    );
+    if let Some(preview) = ui_state.source_preview.as_mut().filter(|preview| preview.visible) {
+        let inner = image_frame.inner(&Margin { vertical: 1, horizontal: 1 });
+        let lines = preview.lines(inner.width, inner.height).to_vec();
+        f.render_widget(Paragraph::new(lines).block(create_block(preview.title())), image_frame);
+    } else {
+        let ruler = ruler_line(app, ui_state);
+        if let Some(((other_ruler_frame, other_frame), (vertical, horizontal))) = other_pattern_frame {
+            f.render_widget(Paragraph::new(ruler.clone()).scroll((0, horizontal as u16)), other_ruler_frame);
+            let mut vertical_scroll = ScrollbarState::new(app.lines.len()).position(vertical);
+            let mut horizontal_scroll = ui_state.horizontal_scroll.position(horizontal);
+            render_pattern_pane(
+                f,
+                other_frame,
+                Paragraph::new(text.clone()).block(create_block("Pattern").dark_gray()),
+                (vertical, horizontal),
+                &mut vertical_scroll,
+                &mut horizontal_scroll,
+            );
+        }
+        let title = if ui_state.split.is_some() { "Pattern (active, Tab: switch)" } else { "Pattern" };
+        f.render_widget(Paragraph::new(ruler).scroll((0, ui_state.horizontal_scroll_amount as u16)), ruler_frame);
+        render_pattern_pane(
+            f,
+            pattern_frame,
+            Paragraph::new(text).block(create_block(title)),
+            (ui_state.vertical_scroll_amount, ui_state.horizontal_scroll_amount),
+            &mut ui_state.vertical_scroll,
+            &mut ui_state.horizontal_scroll,
+        );
+    }
+    if app.is_done() {
+        let summary = Paragraph::new(vec![
+            Line::from("Pattern complete!".bold()),
+            Line::from(format!("{} links woven", app.total_links())),
+            Line::from(format!(
+                "This session: {}",
+                format_duration(app.session_start.elapsed())
+            )),
+            Line::from("Press r to start over or q to quit."),
+        ])
+        .alignment(Alignment::Center)
+        .block(create_block("Done").green());
+        let [_, summary_area, _] = Layout::vertical([
+            Constraint::Fill(1),
+            Constraint::Length(6),
+            Constraint::Fill(1),
+        ])
+        .areas(image_frame);
+        f.render_widget(Clear, summary_area);
+        f.render_widget(summary, summary_area);
+    } else if app.just_completed_row {
+        let banner = Paragraph::new(format!(
+            "Row {} complete! Attach it, then press Space to continue.",
+            app.progress.row
+        ))
+        .alignment(Alignment::Center)
+        .block(create_block("Row complete").yellow());
+        let [_, banner_area, _] = Layout::vertical([
+            Constraint::Fill(1),
+            Constraint::Length(3),
+            Constraint::Fill(1),
+        ])
+        .areas(image_frame);
+        f.render_widget(Clear, banner_area);
+        f.render_widget(banner, banner_area);
+    }
+    if let Some(selected) = ui_state.color_list {
+        render_color_list(f, image_frame, app, ui_state, config, selected);
+    }
 
-    let render_color_box = |f: &mut Frame, color: &Rgb8, bounds: &Rect, color_map: &ColorMap| {
+    let render_color_box = |f: &mut Frame, color: &Rgb8, bounds: &Rect, color_map: &ColorMap, run: Option<usize>, index: Option<(usize, usize)>| {
+        let run = run.map(|len| format!(" x{}", len)).unwrap_or_default();
+        let index = index.map(|(row, link)| format!(" - row {}, link {}", row, link)).unwrap_or_default();
         let canvas = Canvas::default()
-            .block(create_block_owned(format!("Current link: {}", color_map.full_name(*color))))
+            .block(create_block_owned(format!(
+                "Current link: {}{} ({}){}",
+                color_map.full_name(*color).unwrap_or(UNMAPPED_NAME),
+                run,
+                color.to_hex(),
+                index
+            )))
             .background_color(Color::Rgb(color.0[0], color.0[1], color.0[2]))
             .x_bounds([
                 0., bounds.width as f64
@@ -519,21 +3264,22 @@ fn ui(f: &mut Frame, app: &mut App, ui_state: &mut UIState, color_map: &ColorMap
         f.render_widget(canvas, *bounds);
     };
 
-    let render_single_pixel_preview = |f: &mut Frame, pixel: Option<Rgb8>, bounds: &Rect, empty_block_name: &'static str| {
+    let render_single_pixel_preview = |f: &mut Frame, pixel: Option<Rgb8>, bounds: &Rect, empty_block_name: &'static str, empty_text: &'static str, run: Option<usize>, index: Option<(usize, usize)>| {
         if let Some(current_color) = pixel {
-            render_color_box(f, &current_color, bounds, color_map);
+            render_color_box(f, &current_color, bounds, color_map, run, index);
         } else {
-            let para = Paragraph::new("End of line")
+            let para = Paragraph::new(empty_text)
                 .block(create_block(empty_block_name));
             f.render_widget(para, *bounds);
         }
     };
-    let render_tri_pixel_preview = |f: &mut Frame, pixels: [Option<Rgb8>; 3], base_bounds: &Rect| {
-        let tri_box: [Rect; 3] = tri_box_layout.areas(*base_bounds);
+    let render_multi_pixel_preview = |f: &mut Frame, pixels: &[Option<Rgb8>], base_bounds: &Rect| {
+        let count = pixels.len() as u32;
+        let boxes = Layout::vertical(vec![Constraint::Ratio(1, count); pixels.len()]).split(*base_bounds);
 
-        for (bound, pixel) in tri_box.iter().zip(pixels.iter()) {
+        for (bound, pixel) in boxes.iter().zip(pixels.iter()) {
             if let Some(pixel) = pixel {
-                render_color_box(f, pixel, bound, color_map);
+                render_color_box(f, pixel, bound, color_map, None, None);
             } else {
                 let para = Paragraph::new("End of line")
                     .block(create_block("Link"));
@@ -541,19 +3287,212 @@ fn ui(f: &mut Frame, app: &mut App, ui_state: &mut UIState, color_map: &ColorMap
             }
         }
     };
-    match app.current_pixel {
-        Pixel(pixel) => render_single_pixel_preview(f, pixel, &current_color_box, "Current link"),
-        Tri(pixels) => render_tri_pixel_preview(f, pixels, &current_color_box),
+    // How many links of the same color are left in the row, counting the previewed one.
+    let (current_run, next_run) = if ui_state.show_runs {
+        let col = app.progress.col;
+        (col.checked_sub(1).and_then(|col| app.run_from(col)), app.run_from(col))
+    } else {
+        (None, None)
+    };
+    let current_index = if ui_state.show_indices { Some(app.display_position()) } else { None };
+    match &app.prev_pixel {
+        Pixel(pixel) => render_single_pixel_preview(f, *pixel, &prev_color_box, "Previous link", "No previous link", None, None),
+        Multi(pixels) => render_multi_pixel_preview(f, pixels, &prev_color_box),
+    }
+    match &app.current_pixel {
+        Pixel(pixel) => render_single_pixel_preview(f, *pixel, &current_color_box, "Current link", "End of line", current_run, current_index),
+        Multi(pixels) => render_multi_pixel_preview(f, pixels, &current_color_box),
+    }
+    match &app.next_pixel {
+        Pixel(pixel) => render_single_pixel_preview(f, *pixel, &next_color_box, "Next link", "End of line", next_run, None),
+        Multi(pixels) => render_multi_pixel_preview(f, pixels, &next_color_box),
+    }
+
+    let rate = match (app.links_per_minute(), app.eta()) {
+        (Some(rate), Some(eta)) => format!("{:.1} links/min | ETA {}", rate, format_duration(eta)),
+        _ => "-- links/min | ETA --".to_owned(),
+    };
+    let mut stats = format!("{} since break | {}", app.links_since_break, rate);
+    if let Some(count) = ui_state.count {
+        stats = format!("Count {} | {}", count, stats);
     }
-    match app.next_pixel {
-        Pixel(pixel) => render_single_pixel_preview(f, pixel, &next_color_box, "Next link"),
-        Tri(pixels) => render_tri_pixel_preview(f, pixels, &next_color_box),
+    let [controls_line, stats_line] = Layout::horizontal([
+        Constraint::Min(0),
+        Constraint::Length(stats.len() as u16),
+    ])
+    .areas(instruction_line);
+    let current_note = app.current_link().and_then(|link| notes.get(&link));
+    let controls = match (&ui_state.input, ui_state.pending_bookmark, current_note) {
+        (None, None, _) if ui_state.confirming_quit => {
+            Line::from(format!("Quit? {}: quit, any other key: keep going", bound_keys(&config.keymap, Action::Quit)))
+        },
+        (None, None, _) if ui_state.warning.is_some() => {
+            Line::from(ui_state.warning.clone().unwrap_or_default().red().bold())
+        },
+        (None, None, Some(note)) => Line::from(format!("Note: {}", note).yellow().bold()),
+        (Some(input @ TextInput { purpose: InputPurpose::ColorChar(..), .. }), _, _) => Line::from(format!(
+            "{} (Enter: confirm, Backspace when empty: back to name, Esc: cancel): {}",
+            input.prompt(),
+            input.buffer
+        )),
+        (Some(input), _, _) => Line::from(format!("{} (Enter: confirm, Esc: cancel): {}", input.prompt(), input.buffer)),
+        (None, Some(BookmarkAction::Set), _) => Line::from("Set bookmark: press a key to name it"),
+        (None, Some(BookmarkAction::Jump), _) => {
+            let mut names: Vec<&str> = bookmarks.keys().map(String::as_str).collect();
+            names.sort();
+            Line::from(format!("Jump to bookmark: {}", names.join(" ")))
+        }
+        (None, None, None) => Line::from(ui_state.controls_help.as_str()),
+    };
+    f.render_widget(controls, controls_line);
+    f.render_widget(Line::from(stats), stats_line);
+}
+
+// The popup title, naming whatever keys the keymap currently binds.
+fn color_list_title(keymap: &HashMap<String, Action>) -> String {
+    let keys = |action| bound_keys(keymap, action);
+    format!(
+        "Colors ({}/{}: move, Enter/{}: focus, {}: have enough, Esc/{}: close)",
+        keys(Action::ScrollDown),
+        keys(Action::ScrollUp),
+        keys(Action::FocusColor),
+        keys(Action::ToggleLinkDone),
+        keys(Action::ColorList),
+    )
+}
+
+// A popup listing every named color with how many of its links are left, as a shopping list.
+// Enter or the focus key focuses the highlighted color; the link-done key marks it as already
+// on hand, which strikes it through and leaves it out of the total.
+fn render_color_list(
+    f: &mut Frame,
+    area: Rect,
+    app: &App,
+    ui_state: &UIState,
+    config: &Config,
+    selected: usize,
+) {
+    let entries = config.color_map.sorted_entries();
+    let remaining = app.remaining_by_color();
+    let needed: usize = remaining
+        .iter()
+        .filter(|(color, _)| !config.completed_colors.contains(color))
+        .map(|(_, count)| count)
+        .sum();
+    let lines: Vec<Line> = entries
+        .iter()
+        .enumerate()
+        .map(|(idx, (color, full_name, one_char))| {
+            let swatch = Style::new().fg(Color::Rgb(color.0[0], color.0[1], color.0[2]));
+            let focus = if ui_state.focused_colors.contains(color) { "*" } else { " " };
+            let text_style = if config.completed_colors.contains(color) {
+                Style::new().add_modifier(Modifier::CROSSED_OUT | Modifier::DIM)
+            } else {
+                Style::new()
+            };
+            let line = Line::from(vec![
+                Span::styled("\u{2588}\u{2588} ", swatch),
+                Span::styled(
+                    format!(
+                        "{} {} {:<20} {} {:>6} left",
+                        focus,
+                        one_char,
+                        full_name,
+                        color.to_hex(),
+                        remaining.get(color).copied().unwrap_or(0)
+                    ),
+                    text_style,
+                ),
+            ]);
+            if idx == selected {
+                line.reversed()
+            } else {
+                line
+            }
+        })
+        .collect();
+
+    let height = (lines.len() as u16 + 2).min(area.height);
+    let [_, list_area, _] = Layout::vertical([Constraint::Fill(1), Constraint::Length(height), Constraint::Fill(1)]).areas(area);
+    let [_, list_area, _] = Layout::horizontal([Constraint::Fill(1), Constraint::Length(66), Constraint::Fill(1)]).areas(list_area);
+    // Keep the highlighted entry in view when the list is taller than the popup.
+    let scroll = selected.saturating_sub(height.saturating_sub(3) as usize);
+    let list = Paragraph::new(lines)
+        .scroll((scroll as u16, 0))
+        .block(
+            Block::bordered()
+                .title(color_list_title(&config.keymap).bold())
+                .title_bottom(format!("{} links still needed", needed)),
+        );
+    f.render_widget(Clear, list_area);
+    f.render_widget(list, list_area);
+}
+
+// Column numbers for the pattern pane, laid out like an unstaggered row: a tick every 5 links and
+// a number every 10, with a marker over the current link (following its row's stagger).
+fn ruler_line(app: &App, ui_state: &UIState) -> Line<'static> {
+    let period = ui_state.cell_period();
+    let longest = app.rows.iter().map(|r| r.len()).max().unwrap_or(0);
+    let mut ruler = vec![' '; ROW_GUTTER_WIDTH + longest * period + period];
+    for col in (4..longest).step_by(5) {
+        let x = ROW_GUTTER_WIDTH + col * period;
+        if (col + 1) % 10 == 0 {
+            for (slot, digit) in ruler[x..].iter_mut().zip((col + 1).to_string().chars()) {
+                *slot = digit;
+            }
+        } else {
+            ruler[x] = '\u{2502}';
+        }
     }
+    let Some((row, col)) = app.current_link() else {
+        return Line::from(ruler.into_iter().collect::<String>()).dark_gray();
+    };
+    let staggered = is_staggered(row, stagger_flipped(ui_state.stagger_parity, app.mirrored));
+    let x = ROW_GUTTER_WIDTH + if staggered { period / 2 } else { 0 } + col * period;
+    let before: String = ruler[..x].iter().collect();
+    let after: String = ruler[x + 1..].iter().collect();
+    Line::from(vec![
+        Span::raw(before).dark_gray(),
+        Span::raw("\u{25bc}").yellow().bold(),
+        Span::raw(after).dark_gray(),
+    ])
+}
 
-    let controls = Line::from(
-        "q: Quit | Space: Next link | arrows/h/j/k/l: Scroll left/down/up/right | r: Reset progress",
+// Draws one view of the pattern scrolled to (vertical, horizontal), with its scrollbars.
+fn render_pattern_pane(
+    f: &mut Frame,
+    area: Rect,
+    para: Paragraph,
+    (vertical, horizontal): (usize, usize),
+    vertical_scroll: &mut ScrollbarState,
+    horizontal_scroll: &mut ScrollbarState,
+) {
+    f.render_widget(para.scroll((vertical as u16, horizontal as u16)), area);
+    f.render_stateful_widget(
+        Scrollbar::new(ScrollbarOrientation::HorizontalBottom),
+        area.inner(&Margin {
+            vertical: 0,
+            horizontal: 1,
+        }),
+        horizontal_scroll,
+    );
+    f.render_stateful_widget(
+        Scrollbar::new(ScrollbarOrientation::VerticalRight),
+        area.inner(&Margin {
+            vertical: 1,
+            horizontal: 0,
+        }),
+        vertical_scroll,
     );
-    f.render_widget(controls, instruction_line);
+}
+
+fn format_duration(duration: Duration) -> String {
+    let minutes = duration.as_secs() / 60;
+    if minutes >= 60 {
+        format!("{}h {:02}m", minutes / 60, minutes % 60)
+    } else {
+        format!("{}m", minutes)
+    }
 }
 
 
@@ -573,53 +3512,548 @@ fn ensure_scroll_to_visible(frame_size: usize, content_length: usize, current_sc
     }
 }
 
-fn print_grid(rows: Vec<Vec<Rgb8>>, color_map: &mut ColorMap) {
+// Rows are staggered like the TUI's, flipped or not. With `woven`, links that app has woven are
+// bracketed, which unlike case also shows for digits and symbols. The offset is where `rows`
+// starts in the app's pattern, for pages.
+fn print_grid(
+    rows: &[Vec<Rgb8>],
+    color_map: &ColorMap,
+    flipped: bool,
+    woven: Option<(&App, (usize, usize))>,
+) -> io::Result<()> {
     use colored::Colorize;
-    for (row_idx, row) in rows.into_iter().enumerate() {
-        if row_idx % 2 == 1 {
-            print!(" ");
+    use io::Write;
+
+    let mut out = io::stdout().lock();
+    for (row_idx, row) in rows.iter().enumerate() {
+        if let Some((app, (top, _))) = woven {
+            let marker = if app.is_row_in_progress(top + row_idx) { ">" } else { " " };
+            write!(out, "{} ", marker)?;
         }
-        for p in row {
-            let colored_p = color_map
-                .one_char(p)
+        if is_staggered(row_idx, flipped) {
+            // Room for brackets doubles each link's width, and the stagger with it.
+            write!(out, "{}", if woven.is_some() { "  " } else { " " })?;
+        }
+        for (col_idx, &p) in row.iter().enumerate() {
+            let one_char = color_map.one_char(p).unwrap_or(UNMAPPED_CHAR);
+            let cell = match woven {
+                Some((app, (top, left))) if app.is_woven(top + row_idx, left + col_idx) => format!("[{}]", one_char),
+                Some(_) => format!(" {} ", one_char),
+                None => one_char.to_owned(),
+            };
+            let colored_p = cell
                 .color(rgb8_to_true(p))
                 .on_color(rgb8_to_true(SEPARATOR_COLOR));
-            print!("{} ", colored_p);
+            write!(out, "{} ", colored_p)?;
         }
-        println!();
+        writeln!(out)?;
     }
+    Ok(())
 }
 
-fn flood_fill(img: &mut RgbImage, (x, y): (u32, u32)) {
-    if img[(x, y)].to_rgb8() == SEPARATOR_COLOR {
-        return;
+// Marks the blob containing `start` in `visited`, a row-major flag per pixel, returning how many
+// pixels were filled. Zero means the start pixel was a separator or already visited. Each marked
+// pixel uses up one of `budget`; once it runs out the fill stops short. Pixels waiting to be
+// filled go on a heap-allocated stack, so even a huge region can't overflow the call stack.
+fn flood_fill_region(
+    img: &RgbImage,
+    visited: &mut [bool],
+    start: (u32, u32),
+    is_separator: &dyn Fn(Rgb8) -> bool,
+    connectivity: Connectivity,
+    budget: &mut usize,
+) -> usize {
+    const SIDES: [(i32, i32); 4] = [(-1, 0), (0, -1), (1, 0), (0, 1)];
+    const CORNERS: [(i32, i32); 4] = [(-1, -1), (1, -1), (-1, 1), (1, 1)];
+    let corners: &[(i32, i32)] = if connectivity == Connectivity::Eight { &CORNERS } else { &[] };
+    let mut filled = 0;
+    let mut pending = vec![start];
+    while let Some((x, y)) = pending.pop() {
+        let idx = (y * img.width() + x) as usize;
+        if visited[idx] || is_separator(img[(x, y)].to_rgb8()) {
+            continue;
+        }
+        if *budget == 0 {
+            break;
+        }
+        *budget -= 1;
+        visited[idx] = true;
+        filled += 1;
+        for (dx, dy) in SIDES.iter().chain(corners) {
+            if let (Some(nx), Some(ny)) = (x.checked_add_signed(*dx), y.checked_add_signed(*dy)) {
+                if nx < img.width() && ny < img.height() {
+                    pending.push((nx, ny));
+                }
+            }
+        }
+    }
+    filled
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gpl_palette_format() {
+        let mut color_map = ColorMap::new();
+        color_map.add_entry(Rgb8([255, 0, 16]), "Red", "R");
+        color_map.add_entry(Rgb8([0, 0, 0]), "Black", "K");
+        assert_eq!(
+            color_map.to_gpl("Test"),
+            "GIMP Palette\n\
+             Name: Test\n\
+             #\n\
+             # #000000\n  0   0   0\tBlack\n\
+             # #FF0010\n255   0  16\tRed\n"
+        );
     }
-    img[(x, y)] = Rgb(SEPARATOR_COLOR.0);
 
-    if x > 0 {
-        flood_fill(img, (x - 1, y));
+    #[test]
+    fn svg_has_a_hexagon_per_link() {
+        let mut color_map = ColorMap::new();
+        color_map.add_entry(Rgb8([255, 0, 16]), "Red", "<");
+        let rows = vec![vec![Rgb8([255, 0, 16]); 3], vec![Rgb8([255, 0, 16]); 2]];
+        let svg = export::export_svg(&rows, &color_map, 24.0, false, None);
+        assert_eq!(svg.matches("<polygon").count(), 5);
+        assert_eq!(svg.matches(">&lt;</text>").count(), 5);
     }
-    if y > 0 {
-        flood_fill(img, (x, y - 1));
+
+    #[test]
+    fn every_action_has_a_default_key() {
+        let keymap = default_keymap();
+        for action in Action::ALL {
+            assert!(keymap.values().any(|bound| *bound == action), "{:?} is unbound", action);
+        }
+        assert!(controls_help(&keymap).starts_with("q: Quit | Space: [count] Next link(s) | "));
     }
-    if x + 1 < img.width() {
-        flood_fill(img, (x + 1, y));
+
+    #[test]
+    fn color_list_title_follows_the_keymap() {
+        let mut keymap = default_keymap();
+        assert_eq!(
+            color_list_title(&keymap),
+            "Colors (j/Down/k/Up: move, Enter/f: focus, x: have enough, Esc/C: close)"
+        );
+        keymap.retain(|_, action| *action != Action::ScrollDown);
+        keymap.insert("t".to_string(), Action::ScrollDown);
+        assert!(color_list_title(&keymap).starts_with("Colors (t/k/Up: move"));
     }
-    if y + 1 < img.height() {
-        flood_fill(img, (x, y + 1));
+
+    #[test]
+    fn pages_overlap_and_start_on_even_rows() {
+        let rows = vec![vec![RED; 10]; 7];
+        let pages = export::paginate(&rows, 4, 6, 1).unwrap();
+        let corners: Vec<_> = pages.iter().map(|page| (page.top, page.left)).collect();
+        assert_eq!(corners, vec![(0, 0), (0, 5), (2, 0), (2, 5), (4, 0), (4, 5)]);
+        assert!(pages.iter().all(|page| page.grid == (3, 2)));
+        assert_eq!(pages[5].rows, vec![vec![RED; 5]; 3]);
+        assert_eq!(pages[5].header(), "Page 3-2 of 3x2  rows 5-7  links 6-10");
     }
-}
 
-fn append_to_log<T: ToString>(s: T) -> Result<(), Box<dyn Error>> {
-    use std::fs::OpenOptions;
-    use std::io::prelude::*;
+    const RED: Rgb8 = Rgb8([255, 0, 0]);
+    const GREEN: Rgb8 = Rgb8([0, 255, 0]);
+    const BLUE: Rgb8 = Rgb8([0, 0, 255]);
 
-    let mut file = OpenOptions::new()
-        .write(true)
-        .append(true)
-        .create(true)
-        .open("log")?;
+    // Square cells of `cell_size` pixels with 1px separator lines around and between them.
+    fn grid_image(cells: &[&[Rgb8]], cell_size: u32) -> RgbImage {
+        let width = cells.iter().map(|row| row.len()).max().unwrap_or(0) as u32;
+        let mut img = RgbImage::from_pixel(
+            width * (cell_size + 1) + 1,
+            cells.len() as u32 * (cell_size + 1) + 1,
+            Rgb(SEPARATOR_COLOR.0),
+        );
+        for (row_idx, row) in cells.iter().enumerate() {
+            for (col_idx, color) in row.iter().enumerate() {
+                let x0 = col_idx as u32 * (cell_size + 1) + 1;
+                let y0 = row_idx as u32 * (cell_size + 1) + 1;
+                for y in y0..(y0 + cell_size) {
+                    for x in x0..(x0 + cell_size) {
+                        img[(x, y)] = Rgb(color.0);
+                    }
+                }
+            }
+        }
+        img
+    }
 
-    writeln!(file, "{}", s.to_string())?;
-    Ok(())
+    fn builder_for(img: RgbImage) -> RowBuilder {
+        RowBuilder::new(img, default_separators(), HashSet::new(), Connectivity::Four, PixelMode::Blob)
+    }
+
+    fn named(colors: &[Rgb8]) -> ColorMap {
+        let mut color_map = ColorMap::new();
+        for (idx, color) in colors.iter().enumerate() {
+            color_map.add_entry(*color, &format!("Color {}", idx), &idx.to_string());
+        }
+        color_map
+    }
+
+    #[test]
+    fn builds_rows_from_a_grid() {
+        let cells: &[&[Rgb8]] = &[&[RED, GREEN, BLUE], &[GREEN, BLUE, RED], &[BLUE, RED, GREEN]];
+        let builder = builder_for(grid_image(cells, 4));
+        let rows = builder.build(&mut named(&[RED, GREEN, BLUE])).unwrap();
+        assert_eq!(rows, cells.iter().map(|row| row.to_vec()).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn black_links_are_kept() {
+        const BLACK: Rgb8 = Rgb8([0, 0, 0]);
+        let cells: &[&[Rgb8]] = &[&[RED, BLACK, RED], &[BLACK, BLACK]];
+        let builder = builder_for(grid_image(cells, 4));
+        let rows = builder.build(&mut named(&[RED, BLACK])).unwrap();
+        assert_eq!(rows, cells.iter().map(|row| row.to_vec()).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn blank_bands_leave_no_empty_rows() {
+        let mut img = grid_image(&[&[RED, GREEN], &[GREEN, RED]], 3);
+        // Stretch the image with a band of outline between the two rows.
+        let mut tall = RgbImage::from_pixel(img.width(), img.height() + 10, Rgb(SEPARATOR_COLOR.0));
+        image::imageops::replace(&mut tall, &image::imageops::crop(&mut img, 0, 0, 9, 4).to_image(), 0, 0);
+        image::imageops::replace(&mut tall, &image::imageops::crop(&mut img, 0, 4, 9, 5).to_image(), 0, 14);
+        let rows = builder_for(tall).build(&mut named(&[RED, GREEN])).unwrap();
+        assert_eq!(rows, vec![vec![RED, GREEN], vec![GREEN, RED]]);
+    }
+
+    #[test]
+    fn rows_keep_their_own_lengths() {
+        let cells: &[&[Rgb8]] = &[&[RED, RED, RED, RED], &[GREEN, GREEN], &[BLUE]];
+        let builder = builder_for(grid_image(cells, 3));
+        let rows = builder.build(&mut named(&[RED, GREEN, BLUE])).unwrap();
+        assert_eq!(rows.iter().map(|r| r.len()).collect::<Vec<_>>(), vec![4, 2, 1]);
+    }
+
+    #[test]
+    fn region_sizes_match_the_cells() {
+        let cells: &[&[Rgb8]] = &[&[RED, GREEN], &[BLUE, RED]];
+        let builder = builder_for(grid_image(cells, 5));
+        let mut sizes = vec![];
+        builder.build_with_region_sizes(&mut named(&[RED, GREEN, BLUE]), &mut sizes).unwrap();
+        assert_eq!(sizes, vec![vec![25, 25], vec![25, 25]]);
+    }
+
+    #[test]
+    fn unknown_colors_are_found_in_scan_order() {
+        let cells: &[&[Rgb8]] = &[&[GREEN, RED], &[BLUE, GREEN]];
+        let builder = builder_for(grid_image(cells, 4));
+        let colors = builder.discover_colors();
+        assert_eq!(colors, vec![GREEN, RED, BLUE]);
+        assert_eq!(named(&[RED]).count_unmapped(&colors), 2);
+    }
+
+    #[test]
+    fn background_colors_are_skipped() {
+        let cells: &[&[Rgb8]] = &[&[BLUE, RED, BLUE], &[BLUE, GREEN, BLUE]];
+        let mut builder = builder_for(grid_image(cells, 4));
+        builder.background_colors.insert(BLUE);
+        assert_eq!(builder.discover_colors(), vec![RED, GREEN]);
+        let rows = builder.build(&mut named(&[RED, GREEN])).unwrap();
+        assert_eq!(rows, vec![vec![RED], vec![GREEN]]);
+    }
+
+    #[test]
+    fn rle_collapses_neighbouring_links() {
+        assert_eq!(rle_row(&[]), vec![]);
+        assert_eq!(
+            rle_row(&[RED, RED, BLUE, RED, RED, RED]),
+            vec![(RED, 2), (BLUE, 1), (RED, 3)]
+        );
+    }
+
+    #[test]
+    fn per_pixel_mode_makes_every_pixel_a_link() {
+        let mut img = RgbImage::from_pixel(3, 2, Rgb(RED.0));
+        img[(1, 0)] = Rgb(GREEN.0);
+        img[(2, 1)] = Rgb(SEPARATOR_COLOR.0);
+        let builder = RowBuilder::new(img, default_separators(), HashSet::new(), Connectivity::Four, PixelMode::PerPixel);
+        assert_eq!(builder.estimate_links(), 5);
+        let rows = builder.build(&mut named(&[RED, GREEN])).unwrap();
+        assert_eq!(rows, vec![vec![RED, GREEN, RED], vec![RED, RED]]);
+    }
+
+    #[test]
+    fn separator_predicate_accepts_any_gray() {
+        let mut img = grid_image(&[&[RED, GREEN, BLUE]], 3);
+        // Lighten part of the outline so no single separator color covers it.
+        for y in 0..img.height() {
+            img[(4, y)] = Rgb([128, 128, 128]);
+        }
+        let is_gray: SeparatorPredicate = Box::new(|Rgb8([r, g, b])| r == g && g == b);
+        let builder = RowBuilder::with_separator_predicate(img, is_gray);
+        assert_eq!(builder.discover_colors(), vec![RED, GREEN, BLUE]);
+        let rows = builder.build(&mut named(&[RED, GREEN, BLUE])).unwrap();
+        assert_eq!(rows, vec![vec![RED, GREEN, BLUE]]);
+    }
+
+    #[test]
+    fn jump_targets_are_one_based_and_bounded() {
+        let rows = vec![vec![RED; 4], vec![RED; 6]];
+        assert_eq!(parse_jump_target("2", &rows), Some((1, 0)));
+        assert_eq!(parse_jump_target("2, 6", &rows), Some((1, 6)));
+        assert_eq!(parse_jump_target("1,5", &rows), None);
+        assert_eq!(parse_jump_target("3", &rows), None);
+        assert_eq!(parse_jump_target("0", &rows), None);
+        assert_eq!(parse_jump_target("", &rows), None);
+    }
+
+    #[test]
+    fn content_bounds_skip_outline_borders() {
+        let img = grid_image(&[&[RED, GREEN], &[BLUE]], 3);
+        let is_separator = |color: Rgb8| color == SEPARATOR_COLOR;
+        assert_eq!(content_bounds(&img, is_separator), Some((1, 1, 7, 7)));
+        let blank = RgbImage::from_pixel(5, 5, Rgb(SEPARATOR_COLOR.0));
+        assert_eq!(content_bounds(&blank, is_separator), None);
+    }
+
+    #[test]
+    fn verify_reports_drift_both_ways() {
+        let report = verify(&[RED, BLUE], &named(&[RED, GREEN]));
+        assert_eq!(report.unmapped, vec![BLUE]);
+        assert_eq!(report.unused.len(), 1);
+        assert_eq!(report.unused[0].0, GREEN);
+    }
+
+    #[test]
+    fn nearest_entry_picks_the_closest_named_color() {
+        let color_map = named(&[RED, GREEN]);
+        let (near, full_name, _) = color_map.nearest_entry(Rgb8([240, 20, 0])).unwrap();
+        assert_eq!((near, full_name), (RED, "Color 0"));
+        assert_eq!(near.distance(Rgb8([240, 20, 0])), 20);
+        assert!(ColorMap::new().nearest_entry(RED).is_none());
+    }
+
+    #[test]
+    fn text_chart_is_unstaggered_with_a_legend() {
+        let rows = vec![vec![RED, GREEN, RED], vec![GREEN, GREEN]];
+        let chart = export::render_chart(&rows, &named(&[RED, GREEN]), export::ChartFormat::Text);
+        let lines: Vec<&str> = chart.lines().collect();
+        assert_eq!(&lines[..3], &["  1", "1 0 1 0", "2 1 1"]);
+        assert!(chart.ends_with("Legend:\n  0  #FF0000  Color 0  2\n  1  #00FF00  Color 1  3\n"));
+    }
+
+    #[test]
+    fn remaining_rows_start_partway_through_the_current_row() {
+        let rows = vec![vec![RED; 4], vec![GREEN; 4], vec![BLUE, RED, GREEN, BLUE], vec![RED; 3]];
+        let progress = Progress { row: 2, col: 3, ..Progress::at_start(1) };
+        assert_eq!(remaining_rows(&rows, &progress, 1), vec![vec![GREEN, BLUE], vec![RED; 3]]);
+        let odd_row = Progress { row: 3, col: 2, ..Progress::at_start(1) };
+        assert_eq!(remaining_rows(&rows, &odd_row, 1), vec![vec![], vec![RED; 2]]);
+        // During the seed phase each seed row has its own share left.
+        let seeding = Progress { row: 1, col: 2, ..Progress::at_start(2) };
+        assert_eq!(remaining_rows(&rows, &seeding, 2)[..2], [vec![RED; 2], vec![GREEN; 3]]);
+        // Nothing is woven yet at the start, whatever the seed phase reveals.
+        assert_eq!(remaining_rows(&rows, &Progress::at_start(3), 3), rows);
+    }
+
+    #[test]
+    fn oversized_regions_are_an_error() {
+        let mut builder = builder_for(grid_image(&[&[RED, GREEN]], 4));
+        builder.max_region = 15;
+        let err = builder.build(&mut named(&[RED, GREEN])).unwrap_err();
+        assert!(err.to_string().contains("more than 15 pixels"));
+        builder.max_region = 16;
+        assert!(builder.build(&mut named(&[RED, GREEN])).is_ok());
+    }
+
+    #[test]
+    fn all_separator_image_is_an_error() {
+        let builder = builder_for(RgbImage::from_pixel(4, 4, Rgb(SEPARATOR_COLOR.0)));
+        let err = builder.build(&mut ColorMap::new()).unwrap_err();
+        assert!(err.to_string().contains("No non-separator pixels"));
+    }
+
+    #[test]
+    fn eight_connectivity_joins_diagonal_pixels() {
+        // Red pixels touching only at corners, with outline everywhere else.
+        let mut img = RgbImage::from_pixel(3, 3, Rgb(SEPARATOR_COLOR.0));
+        for (x, y) in [(0, 0), (2, 0), (1, 1), (0, 2), (2, 2)] {
+            img[(x, y)] = Rgb(RED.0);
+        }
+        let count_links = |connectivity| {
+            let builder = RowBuilder::new(img.clone(), default_separators(), HashSet::new(), connectivity, PixelMode::Blob);
+            builder.build(&mut named(&[RED])).unwrap().concat().len()
+        };
+        assert_eq!(count_links(Connectivity::Four), 5);
+        assert_eq!(count_links(Connectivity::Eight), 1);
+    }
+
+    #[test]
+    fn color_edits_undo_and_redo_in_order() {
+        let mut color_map = ColorMap::new();
+        color_map.add_entry(RED, "Red", "r");
+        color_map.add_entry(RED, "Crimson", "c");
+
+        assert!(color_map.undo());
+        assert_eq!(color_map.entry(RED), Some(("Red".to_owned(), "r".to_owned())));
+        assert!(color_map.redo());
+        assert_eq!(color_map.entry(RED), Some(("Crimson".to_owned(), "c".to_owned())));
+        assert!(!color_map.redo());
+
+        assert!(color_map.undo());
+        assert!(color_map.undo());
+        assert!(!color_map.is_mapped(RED));
+        assert!(!color_map.undo());
+    }
+
+    #[test]
+    fn trailing_empty_rows_do_not_delay_done() {
+        let rows = vec![vec![RED; 2], vec![RED; 2], vec![]];
+        let mut app = App::new(rows, Progress::at_start(1), false, 1, false, false);
+        let mut ticks = 0;
+        while !app.is_done() {
+            assert!(ticks < 4, "still not done at {:?}", (app.progress.row, app.progress.col));
+            app.tick();
+            ticks += 1;
+        }
+        assert_eq!((app.progress.row, app.progress.col), (1, 1));
+        assert!(App::new(vec![vec![], vec![]], Progress::at_start(1), false, 1, false, false).is_done());
+    }
+
+    #[test]
+    fn every_separator_color_splits_links() {
+        let guide = Rgb8([200, 200, 200]);
+        let mut img = grid_image(&[&[RED, GREEN]], 3);
+        for y in 0..img.height() {
+            img[(4, y)] = Rgb(guide.0);
+        }
+        let builder =
+            RowBuilder::new(img, vec![SEPARATOR_COLOR, guide], HashSet::new(), Connectivity::Four, PixelMode::Blob);
+        assert_eq!(builder.discover_colors(), vec![RED, GREEN]);
+        assert_eq!(builder.build(&mut named(&[RED, GREEN])).unwrap(), vec![vec![RED, GREEN]]);
+    }
+
+    #[test]
+    fn reflow_keeps_progress_on_the_same_links() {
+        let rows = vec![vec![RED, GREEN, BLUE], vec![BLUE, GREEN, RED]];
+        let mut config = Config::new(PathBuf::new());
+        config.seed_rows = 1;
+        config.progress.row = 1;
+        config.progress.col = 1;
+        config.progress.completed.insert((1, 0));
+        config.progress.set_row_done(0, true);
+        config.notes.insert((0, 2), "last blue".to_owned());
+        config.bookmarks.insert("a".to_owned(), Progress { row: 1, col: 2, ..Progress::new() });
+
+        let reflowed = arrange_rows(rows.clone(), NonZeroUsize::new(2), &mut config);
+        assert_eq!(reflowed, vec![vec![RED, GREEN], vec![BLUE, BLUE], vec![GREEN, RED]]);
+        // Both links of the second row are woven, so the weave moves on to the third.
+        assert_eq!((config.progress.row, config.progress.col), (2, 0));
+        assert_eq!(config.progress.completed, HashSet::from([(1, 1)]));
+        assert!(config.progress.is_row_done(0) && !config.progress.is_row_done(1));
+        assert_eq!(config.notes.get(&(1, 0)).map(String::as_str), Some("last blue"));
+        assert_eq!((config.bookmarks["a"].row, config.bookmarks["a"].col), (2, 1));
+
+        assert_eq!(arrange_rows(rows, None, &mut config), vec![vec![RED, GREEN, BLUE], vec![BLUE, GREEN, RED]]);
+        assert_eq!((config.progress.row, config.progress.col), (1, 1));
+        assert_eq!(config.progress.completed, HashSet::from([(1, 0)]));
+        assert!(!config.progress.is_row_done(0));
+        assert_eq!(config.notes.get(&(0, 2)).map(String::as_str), Some("last blue"));
+        assert_eq!((config.bookmarks["a"].row, config.bookmarks["a"].col), (1, 2));
+    }
+
+    #[test]
+    fn unstarted_progress_follows_the_seed_row_count() {
+        let rows = vec![vec![RED; 4]; 5];
+        let mut config = Config::new(PathBuf::new());
+        config.set_seed_rows(1);
+        assert!(status_line(rows.clone(), &config).starts_with("1/5 1/4 "));
+        config.set_seed_rows(3);
+        assert!(status_line(rows.clone(), &config).starts_with("3/5 1/4 "));
+
+        config.progress.col = 2;
+        config.set_seed_rows(1);
+        assert!(status_line(rows, &config).starts_with("3/5 2/4 "));
+    }
+
+    #[test]
+    fn remaining_colors_leave_out_only_woven_links() {
+        let rows = vec![vec![RED; 2], vec![GREEN, BLUE, BLUE]];
+        let app = App::new(rows.clone(), Progress { row: 1, col: 2, ..Progress::at_start(1) }, false, 1, false, false);
+        assert_eq!(app.remaining_by_color(), HashMap::from([(RED, 0), (GREEN, 0), (BLUE, 2)]));
+        let fresh = App::new(rows, Progress::at_start(2), false, 2, false, false);
+        assert_eq!(fresh.remaining_by_color(), HashMap::from([(RED, 2), (GREEN, 1), (BLUE, 2)]));
+    }
+
+    #[test]
+    fn mirroring_keeps_notes_and_bookmarks_on_their_links() {
+        let rows = vec![vec![RED; 4], vec![GREEN; 4]];
+        let mut config = Config::new(PathBuf::new());
+        config.seed_rows = 1;
+        config.notes.insert((1, 0), "first green".to_owned());
+        config.bookmarks.insert("a".to_owned(), Progress { row: 1, col: 1, ..Progress::new() });
+        config.mirror_links(&rows);
+        assert_eq!(config.notes.get(&(1, 3)).map(String::as_str), Some("first green"));
+        assert_eq!(config.bookmarks["a"].col, 4);
+        config.mirror_links(&rows);
+        assert_eq!(config.notes.get(&(1, 0)).map(String::as_str), Some("first green"));
+        assert_eq!(config.bookmarks["a"].col, 1);
+    }
+
+    #[test]
+    fn huge_regions_fill_without_overflowing_the_stack() {
+        let mut builder = builder_for(RgbImage::from_pixel(500, 500, Rgb(RED.0)));
+        builder.max_region = 500 * 500;
+        assert_eq!(builder.build(&mut named(&[RED])).unwrap(), vec![vec![RED]]);
+    }
+
+    #[test]
+    fn woven_links_follow_the_position() {
+        let rows = vec![vec![RED; 3]; 4];
+        let fresh = App::new(rows.clone(), Progress::at_start(3), false, 3, false, false);
+        assert!((0..4).all(|row| (0..3).all(|col| !fresh.is_woven(row, col))));
+        assert!(fresh.is_row_in_progress(2) && !fresh.is_row_in_progress(3));
+
+        let mut progress = Progress { row: 3, col: 2, ..Progress::at_start(3) };
+        progress.completed.insert((3, 2));
+        let app = App::new(rows, progress, false, 3, false, false);
+        assert!(app.is_woven(2, 2) && app.is_woven(3, 0) && app.is_woven(3, 2));
+        assert!(!app.is_woven(3, 1));
+        assert!(app.is_row_in_progress(3) && !app.is_row_in_progress(2));
+    }
+
+    #[test]
+    fn a_mirrored_pattern_has_no_diff_against_itself() {
+        let rows = vec![vec![RED, GREEN, BLUE], vec![GREEN, RED]];
+        let mut config = Config::new(PathBuf::new());
+        config.mirror = true;
+        let arranged = arrange_rows(rows.clone(), None, &mut config);
+        assert_eq!(arranged[0], vec![BLUE, GREEN, RED]);
+        assert!(diff_rows(&arranged, &arranged_like(rows, &config)).is_empty());
+    }
+
+    #[test]
+    fn a_reflowed_pattern_has_no_diff_against_itself() {
+        let rows = vec![vec![RED, GREEN, BLUE], vec![GREEN, RED]];
+        let mut config = Config::new(PathBuf::new());
+        let arranged = arrange_rows(rows.clone(), NonZeroUsize::new(2), &mut config);
+        let other = arranged_like(rows, &config);
+        assert_eq!(other.iter().map(Vec::len).collect::<Vec<_>>(), vec![2, 2, 1]);
+        assert!(diff_rows(&arranged, &other).is_empty());
+    }
+
+    #[test]
+    fn flipped_stagger_shifts_even_rows_in_svg() {
+        let rows = vec![vec![RED], vec![RED]];
+        let first_point = |flipped| {
+            let svg = export::export_svg(&rows, &named(&[RED]), 24.0, flipped, None);
+            let start = svg.find("points=\"").unwrap() + "points=\"".len();
+            svg[start..].split(',').next().unwrap().to_owned()
+        };
+        assert_eq!(first_point(false), "12.00");
+        assert_eq!(first_point(true), "24.00");
+        assert!(stagger_flipped(false, true) && !stagger_flipped(true, true));
+    }
+
+    #[test]
+    fn a_new_row_starts_at_link_one() {
+        let mut app = App::new(vec![vec![RED; 3], vec![GREEN; 3]], Progress::at_start(1), false, 1, false, false);
+        assert_eq!(app.display_position(), (1, 1));
+        app.tick();
+        assert_eq!(app.display_position(), (1, 2));
+        app.tick();
+        assert_eq!((app.progress.row, app.progress.col), (1, 0));
+        assert_eq!(app.display_position(), (2, 1));
+    }
 }